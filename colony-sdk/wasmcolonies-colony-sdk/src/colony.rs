@@ -1 +1,133 @@
+use std::collections::HashMap;
+
+use crate::protocol::{
+    ColonyCommand, CombatReport, ConstructionSiteView, DepositView, EnemyStructureView, Location,
+    MineView, MobileView, OreType, PlayerScore, StructureView, TransitView, UnitType, WeaponView,
+};
+use crate::Game;
+use crate::{__CMDSTACK, __STATE};
+
+/// Information and controls specific to your colony
 pub struct Colony {}
+
+impl Colony {
+    /// The structures owned by your colony, such as its player base
+    pub fn structures(&self) -> Vec<StructureView> {
+        __STATE.read().unwrap().structures.clone()
+    }
+
+    /// Your colony's construction sites and their progress
+    pub fn construction_sites(&self) -> Vec<ConstructionSiteView> {
+        __STATE.read().unwrap().construction_sites.clone()
+    }
+
+    /// The mines owned by your colony and the ore they've stockpiled
+    pub fn mines(&self) -> Vec<MineView> {
+        __STATE.read().unwrap().mines.clone()
+    }
+
+    /// Your colony's mobile units, where they are and where they're headed
+    pub fn mobiles(&self) -> Vec<MobileView> {
+        __STATE.read().unwrap().mobiles.clone()
+    }
+
+    /// Your colony's mobile units that are travelling between satellites
+    pub fn transits(&self) -> Vec<TransitView> {
+        __STATE.read().unwrap().transits.clone()
+    }
+
+    /// Your colony's weapons, including those still under construction
+    pub fn weapons(&self) -> Vec<WeaponView> {
+        __STATE.read().unwrap().weapons.clone()
+    }
+
+    /// Other colonies' structures on the satellites where your colony has a presence
+    pub fn enemy_structures(&self) -> Vec<EnemyStructureView> {
+        __STATE.read().unwrap().enemy_structures.clone()
+    }
+
+    /// The attacks your colony made or suffered during the previous tick
+    pub fn combat_reports(&self) -> Vec<CombatReport> {
+        __STATE.read().unwrap().combat.clone()
+    }
+
+    /// Every colony's score as of the end of the previous tick, highest first
+    pub fn scores(&self) -> Vec<PlayerScore> {
+        __STATE.read().unwrap().scores.clone()
+    }
+
+    /// The ore your colony has collected and not yet spent
+    pub fn inventory(&self) -> HashMap<OreType, u32> {
+        __STATE.read().unwrap().inventory.clone()
+    }
+
+    /// Untapped ore deposits on the satellites where your colony has a presence
+    pub fn known_deposits(&self) -> Vec<DepositView> {
+        __STATE.read().unwrap().deposits.clone()
+    }
+
+    /// Orders the construction of a new unit, paid for from your colony's inventory. Mines
+    /// are built on the untapped deposit nearest to your colony's base, and weapons at the
+    /// base itself
+    pub fn construct(&self, unit: UnitType) {
+        __CMDSTACK
+            .write()
+            .unwrap()
+            .push(ColonyCommand::ConstructUnit(Game::tick(), unit));
+    }
+
+    /// Moves the ore stockpiled at one of your mines into your colony's inventory. A mine
+    /// stops extracting ore once its stockpile is full
+    pub fn collect(&self, mine_id: u64) {
+        __CMDSTACK
+            .write()
+            .unwrap()
+            .push(ColonyCommand::Collect(Game::tick(), mine_id));
+    }
+
+    /// Steers one of your mobile units, which will move `(x, y)` meters every tick until it's
+    /// steered again, reaches the edge of its satellite, or runs into a structure
+    pub fn steer(&self, mobile_id: u64, velocity: (f32, f32)) {
+        __CMDSTACK
+            .write()
+            .unwrap()
+            .push(ColonyCommand::Move(Game::tick(), mobile_id, velocity));
+    }
+
+    /// Launches one of your mobile units towards a location on another satellite, which may
+    /// be in another solar system. The further away it is, the longer the unit is in transit
+    pub fn launch(&self, mobile_id: u64, destination: Location) {
+        __CMDSTACK.write().unwrap().push(ColonyCommand::Launch(
+            Game::tick(),
+            mobile_id,
+            destination,
+        ));
+    }
+
+    /// Fires one of your weapons at another colony's structure, which must be on the same
+    /// satellite and within the weapon's range
+    pub fn attack(&self, weapon_id: u64, structure_id: u64) {
+        __CMDSTACK.write().unwrap().push(ColonyCommand::Attack(
+            Game::tick(),
+            weapon_id,
+            structure_id,
+        ));
+    }
+
+    /// Repairs one of your structures, spending ore on as much of its missing HP as you can
+    /// afford. The HP is restored a little at a time over the following ticks
+    pub fn repair(&self, structure_id: u64) {
+        __CMDSTACK
+            .write()
+            .unwrap()
+            .push(ColonyCommand::Repair(Game::tick(), structure_id));
+    }
+
+    /// Upgrades one of your structures to its next level, raising its max HP and armor
+    pub fn upgrade(&self, structure_id: u64) {
+        __CMDSTACK
+            .write()
+            .unwrap()
+            .push(ColonyCommand::Upgrade(Game::tick(), structure_id));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::Colony;
 use crate::UniverseMap;
+use crate::__STATE;
 
 /// Provides your colony with access to the game world
 pub struct Game {}
@@ -10,6 +11,11 @@ impl Game {
         Colony {}
     }
 
+    /// The game tick currently being played
+    pub fn tick() -> u64 {
+        __STATE.read().unwrap().tick
+    }
+
     /// Access to the stellar navigation view of the universe
     pub fn map() -> UniverseMap {
         UniverseMap {}
@@ -1 +1,17 @@
+use crate::protocol::{SatelliteView, SolarSystemView};
+use crate::__STATE;
+
+/// The layout of the universe: its solar systems and the satellites orbiting them
 pub struct UniverseMap {}
+
+impl UniverseMap {
+    /// Every solar system, placed on the star map in light years
+    pub fn systems(&self) -> Vec<SolarSystemView> {
+        __STATE.read().unwrap().systems.clone()
+    }
+
+    /// Every satellite, with its orbit and the size of its surface
+    pub fn satellites(&self) -> Vec<SatelliteView> {
+        __STATE.read().unwrap().satellites.clone()
+    }
+}
@@ -1,11 +1,21 @@
-use crate::ColonyEvent;
-use eventsourcing::{Aggregate, AggregateState};
-use wasmcolonies_protocol::{ColonyCommand, UnitType};
+use crate::{ColonyEvent, ConstructionRules};
+use eventsourcing::{Aggregate, AggregateState, Error, Kind};
+use wasmcolonies_protocol::{Location, UnitType};
+
+/// An order for a player to build a unit at the given location, as the shard hands it to a
+/// construction site once it knows who sent the colony's command and where the unit goes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstructionOrder {
+    pub tick: u64,
+    pub player_id: String,
+    pub utype: UnitType,
+    pub location: Location,
+}
 
 pub struct ConstructionSite;
 impl Aggregate for ConstructionSite {
     type Event = ColonyEvent;
-    type Command = ColonyCommand;
+    type Command = ConstructionOrder;
     type State = ConstructionSiteData;
 
     fn apply_event(state: &Self::State, evt: &Self::Event) -> eventsourcing::Result<Self::State> {
@@ -18,6 +28,7 @@ impl Aggregate for ConstructionSite {
                 tick,
                 utype,
                 yield_in,
+                ..
             } => ConstructionSiteData {
                 began: *tick,
                 yields: utype.clone(),
@@ -29,24 +40,29 @@ impl Aggregate for ConstructionSite {
     }
 
     fn handle_command(
-        _state: &Self::State,
+        state: &Self::State,
         cmd: &Self::Command,
     ) -> eventsourcing::Result<Vec<Self::Event>> {
-        Ok(match cmd {
-            ColonyCommand::ConstructUnit(tick, ut) => {
-                vec![ColonyEvent::UnitConstructionBegan {
-                    tick: *tick,
-                    utype: ut.clone(),
-                    yield_in: 1000, // TODO: TURN THIS INTO A GAME PARAMETER
-                }]
-            }
-            _ => vec![],
-        })
+        let rule = state.rules.build_rule(&cmd.utype).ok_or_else(|| Error {
+            kind: Kind::CommandFailure(format!("{:?} can't be built", cmd.utype)),
+        })?;
+        // Only the shard can name a mobile unit, so the id is filled in when it records the event
+        Ok(vec![ColonyEvent::UnitConstructionBegan {
+            tick: cmd.tick,
+            player_id: cmd.player_id.clone(),
+            utype: cmd.utype.clone(),
+            location: cmd.location.clone(),
+            yield_in: rule.ticks,
+            cost: rule.cost.clone(),
+            unit_id: None,
+        }])
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ConstructionSiteData {
+    /// The rules construction follows, from the game parameters
+    rules: ConstructionRules,
     id: u64,
     began: u64,
     generation: u64,
@@ -54,6 +70,38 @@ pub struct ConstructionSiteData {
     yields: UnitType,
 }
 
+impl ConstructionSiteData {
+    pub fn new(id: u64, rules: ConstructionRules) -> ConstructionSiteData {
+        ConstructionSiteData {
+            rules,
+            id,
+            began: 0,
+            generation: 0,
+            remaining: 0,
+            yields: UnitType::None,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The tick construction began
+    pub fn began(&self) -> u64 {
+        self.began
+    }
+
+    /// Ticks left until construction completes
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// The unit under construction
+    pub fn yields(&self) -> &UnitType {
+        &self.yields
+    }
+}
+
 impl AggregateState for ConstructionSiteData {
     fn generation(&self) -> u64 {
         self.generation
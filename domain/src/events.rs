@@ -1,17 +1,127 @@
 use crate::DOMAIN_VERSION;
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use wasmcolonies_protocol::UnitType;
+use wasmcolonies_protocol::{Location, OreType, PlayerScore, UnitKind, UnitType};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Event)]
+/// A change to the state of a shard. Replaying a shard's events in order, on top of the universe
+/// generated from its game parameters, rebuilds its world
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Event)]
 #[event_type_version(DOMAIN_VERSION)]
 #[event_source("events://wacolonies.com/events/colony")]
 pub enum ColonyEvent {
     None,
     TickFinished(u64),
+    PlayerJoined {
+        tick: u64,
+        player_id: String,
+        actor_key: String,
+        location: Location,
+        /// The id given to the player's base
+        #[serde(default)]
+        base_id: Option<u64>,
+    },
+    PlayerLeft {
+        tick: u64,
+        player_id: String,
+    },
     UnitConstructionBegan {
         tick: u64,
+        player_id: String,
         utype: UnitType,
+        location: Location,
         yield_in: u64,
+        /// The ore spent on the unit
+        #[serde(default)]
+        cost: HashMap<OreType, u32>,
+        /// The id given to the unit
+        #[serde(default)]
+        unit_id: Option<u64>,
+    },
+    /// Construction of the unit at `location` was completed
+    UnitConstructed {
+        tick: u64,
+        player_id: String,
+        kind: UnitKind,
+        location: Location,
+    },
+    /// Ore moved from the stockpile of the mine at `location` into its owner's inventory
+    OreCollected {
+        tick: u64,
+        player_id: String,
+        location: Location,
+        ore: OreType,
+        qty: u32,
+    },
+    /// The mobile unit `unit_id`, at `location`, was steered to a new velocity, in meters
+    /// per tick
+    UnitSteered {
+        tick: u64,
+        player_id: String,
+        unit_id: u64,
+        location: Location,
+        velocity: (f32, f32),
+    },
+    /// The mobile unit `unit_id`, at `location`, left its satellite, to land at
+    /// `destination` in `arrive_in` ticks
+    UnitLaunched {
+        tick: u64,
+        player_id: String,
+        unit_id: u64,
+        location: Location,
+        destination: Location,
+        arrive_in: u64,
+    },
+    /// The weapon `unit_id`, at `location`, hit another player's structure at `target`
+    StructureAttacked {
+        tick: u64,
+        player_id: String,
+        unit_id: u64,
+        target_player_id: String,
+        location: Location,
+        target: Location,
+        damage: u16,
+    },
+    /// The structure at `location`, belonging to `player_id`, was reduced to 0 HP by
+    /// `destroyed_by` and removed from the game
+    StructureDestroyed {
+        tick: u64,
+        player_id: String,
+        destroyed_by: String,
+        location: Location,
+    },
+    /// Repair of `hp` HP began on the structure at `location`, paid for with `cost`
+    RepairBegan {
+        tick: u64,
+        player_id: String,
+        location: Location,
+        hp: u16,
+        cost: HashMap<OreType, u32>,
+    },
+    /// The structure at `location` was upgraded to `level`, paid for with `cost`
+    StructureUpgraded {
+        tick: u64,
+        player_id: String,
+        location: Location,
+        level: u8,
+        cost: HashMap<OreType, u32>,
+    },
+    /// Every player's score at the end of the tick, highest first
+    ScoresPublished {
+        tick: u64,
+        scores: Vec<PlayerScore>,
+    },
+    /// Enough players had joined for the match to begin, with the given players taking part
+    MatchStarted {
+        tick: u64,
+        players: Vec<String>,
+    },
+    /// The match was decided, in favor of `winners` (none if no one won, more than one if
+    /// they tied), with the final scores
+    MatchEnded {
+        tick: u64,
+        winners: Vec<String>,
+        scores: Vec<PlayerScore>,
     },
 }
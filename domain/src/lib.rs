@@ -3,7 +3,12 @@ extern crate eventsourcing_derive;
 
 mod construction;
 mod events;
+mod rules;
+mod store;
 
 pub const DOMAIN_VERSION: &str = "1.0";
 
+pub use construction::*;
 pub use events::*;
+pub use rules::*;
+pub use store::*;
@@ -0,0 +1,32 @@
+//! Game rules shared by the shard and the domain aggregates, as set by the game parameters
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::{OreType, UnitKind, UnitType};
+
+/// What it takes to build a unit: how many ticks construction lasts, and how much of each ore
+/// it costs
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BuildRule {
+    pub ticks: u64,
+    pub cost: HashMap<OreType, u32>,
+}
+
+/// The build rule for each kind of unit
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConstructionRules {
+    rules: HashMap<UnitKind, BuildRule>,
+}
+
+impl ConstructionRules {
+    /// Sets the rule for units of the given kind, replacing any rule it had
+    pub fn insert(&mut self, kind: UnitKind, rule: BuildRule) {
+        self.rules.insert(kind, rule);
+    }
+
+    /// The rule for building the given unit, if it can be built at all
+    pub fn build_rule(&self, utype: &UnitType) -> Option<&BuildRule> {
+        utype.kind().and_then(|kind| self.rules.get(&kind))
+    }
+}
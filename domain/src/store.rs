@@ -0,0 +1,127 @@
+//! Storage for the events that make up a shard's history
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ColonyEvent;
+
+/// An append-only log of events
+pub trait EventStore: Send + Sync {
+    /// Appends events to the end of the store, in order
+    fn append(&mut self, events: &[ColonyEvent]) -> io::Result<()>;
+
+    /// Every event in the store, oldest first
+    fn events(&self) -> io::Result<Vec<ColonyEvent>>;
+}
+
+/// An event store that lives only as long as the shard process
+#[derive(Debug, Default)]
+pub struct MemoryEventStore {
+    events: Vec<ColonyEvent>,
+}
+
+impl EventStore for MemoryEventStore {
+    fn append(&mut self, events: &[ColonyEvent]) -> io::Result<()> {
+        self.events.extend_from_slice(events);
+        Ok(())
+    }
+
+    fn events(&self) -> io::Result<Vec<ColonyEvent>> {
+        Ok(self.events.clone())
+    }
+}
+
+/// An event store kept in a file, one JSON-encoded event per line. Every append is synced
+/// to disk before it returns
+pub struct FileJournal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl FileJournal {
+    /// Opens the journal at the given path, creating it if it doesn't exist. If the journal
+    /// was left partway through a tick (the shard stopped while writing it), that tick's
+    /// events are discarded so the journal ends on a tick boundary
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileJournal> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let mut offset = 0;
+        let mut complete = 0;
+        for line in contents.split_inclusive('\n') {
+            offset += line.len();
+            if !line.ends_with('\n') {
+                break;
+            }
+            if let ColonyEvent::TickFinished(_) = serde_json::from_str(line)? {
+                complete = offset;
+            }
+        }
+        if complete < contents.len() {
+            file.set_len(complete as u64)?;
+        }
+
+        Ok(FileJournal {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Rewinds the journal to the start of the given tick, discarding every event from that
+    /// tick onwards. Returns the discarded events, oldest first
+    pub fn rewind(&mut self, tick: u64) -> io::Result<Vec<ColonyEvent>> {
+        self.writer.flush()?;
+        let contents = std::fs::read_to_string(&self.path)?;
+        let mut keep = 0;
+        if tick > 0 {
+            let last = ColonyEvent::TickFinished(tick - 1);
+            let mut reached = false;
+            for line in contents.split_inclusive('\n') {
+                keep += line.len();
+                if serde_json::from_str::<ColonyEvent>(line)? == last {
+                    reached = true;
+                    break;
+                }
+            }
+            if !reached {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the journal doesn't reach tick {}", tick),
+                ));
+            }
+        }
+
+        let discarded = contents[keep..]
+            .lines()
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect::<io::Result<Vec<ColonyEvent>>>()?;
+        self.writer.get_ref().set_len(keep as u64)?;
+        self.writer.get_ref().sync_data()?;
+        Ok(discarded)
+    }
+}
+
+impl EventStore for FileJournal {
+    fn append(&mut self, events: &[ColonyEvent]) -> io::Result<()> {
+        for event in events {
+            serde_json::to_writer(&mut self.writer, event)?;
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+
+    fn events(&self) -> io::Result<Vec<ColonyEvent>> {
+        BufReader::new(File::open(&self.path)?)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+}
@@ -0,0 +1,68 @@
+//! Construction sites: the events an order to build begins, and the progress they make
+
+use std::collections::HashMap;
+
+use eventsourcing::Aggregate;
+use wasmcolonies_domain::{
+    BuildRule, ColonyEvent, ConstructionOrder, ConstructionRules, ConstructionSite,
+    ConstructionSiteData,
+};
+use wasmcolonies_protocol::{Location, OreType, UnitKind, UnitType};
+
+fn site() -> ConstructionSiteData {
+    let mut rules = ConstructionRules::default();
+    let mut cost = HashMap::new();
+    cost.insert(OreType::new("Wasmium"), 300);
+    rules.insert(UnitKind::Mine, BuildRule { ticks: 30, cost });
+    ConstructionSiteData::new(1, rules)
+}
+
+fn order(utype: UnitType) -> ConstructionOrder {
+    ConstructionOrder {
+        tick: 5,
+        player_id: "alice".to_string(),
+        utype,
+        location: Location {
+            sys: 0,
+            sat: 1,
+            x: 10.0,
+            y: 20.0,
+        },
+    }
+}
+
+#[test]
+fn orders_begin_construction_for_their_player_and_location() {
+    let mine = UnitType::Mine(OreType::new("Wasmium"));
+    let events = ConstructionSite::handle_command(&site(), &order(mine.clone())).unwrap();
+    match &events[..] {
+        [ColonyEvent::UnitConstructionBegan {
+            tick,
+            player_id,
+            utype,
+            location,
+            yield_in,
+            cost,
+            unit_id,
+        }] => {
+            assert_eq!(*tick, 5);
+            assert_eq!(player_id, "alice");
+            assert_eq!(*utype, mine);
+            assert_eq!(*location, order(mine.clone()).location);
+            assert_eq!(*yield_in, 30);
+            assert_eq!(cost[&OreType::new("Wasmium")], 300);
+            assert_eq!(*unit_id, None);
+        }
+        other => panic!("expected construction to begin, got {:?}", other),
+    }
+
+    let state = ConstructionSite::apply_event(&site(), &events[0]).unwrap();
+    let state = ConstructionSite::apply_event(&state, &ColonyEvent::TickFinished(5)).unwrap();
+    assert_eq!(state.began(), 5);
+    assert_eq!(state.remaining(), 29);
+}
+
+#[test]
+fn units_without_a_rule_can_not_be_ordered() {
+    assert!(ConstructionSite::handle_command(&site(), &order(UnitType::Laser)).is_err());
+}
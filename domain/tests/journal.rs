@@ -0,0 +1,118 @@
+//! Journals kept in files: reopening them, and cutting them back to a tick boundary
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use wasmcolonies_domain::{ColonyEvent, EventStore, FileJournal};
+
+/// A path for a journal of the test's own, with nothing left in it from a previous run
+fn scratch(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "wcdomain-{}-{}.journal",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn left(tick: u64, player_id: &str) -> ColonyEvent {
+    ColonyEvent::PlayerLeft {
+        tick,
+        player_id: player_id.to_string(),
+    }
+}
+
+/// Two finished ticks, with a player leaving in each
+fn two_ticks() -> Vec<ColonyEvent> {
+    vec![
+        left(0, "alice"),
+        ColonyEvent::TickFinished(0),
+        left(1, "bob"),
+        ColonyEvent::TickFinished(1),
+    ]
+}
+
+#[test]
+fn journaled_events_are_there_when_it_is_reopened() {
+    let path = scratch("reopened");
+    FileJournal::open(&path)
+        .unwrap()
+        .append(&two_ticks())
+        .unwrap();
+
+    let mut journal = FileJournal::open(&path).unwrap();
+    assert_eq!(journal.events().unwrap(), two_ticks());
+    journal.append(&[ColonyEvent::TickFinished(2)]).unwrap();
+    assert_eq!(journal.events().unwrap().len(), 5);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn a_tick_left_unfinished_is_discarded_on_opening() {
+    let path = scratch("unfinished");
+    let mut events = two_ticks();
+    events.push(left(2, "carol"));
+    FileJournal::open(&path).unwrap().append(&events).unwrap();
+    // The shard stopped partway through writing an event
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"{\"PlayerLeft\":{\"tick\":2,").unwrap();
+
+    let journal = FileJournal::open(&path).unwrap();
+    assert_eq!(journal.events().unwrap(), two_ticks());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn a_journal_without_a_finished_tick_is_emptied() {
+    let path = scratch("empty");
+    FileJournal::open(&path)
+        .unwrap()
+        .append(&[left(0, "alice")])
+        .unwrap();
+
+    let journal = FileJournal::open(&path).unwrap();
+    assert!(journal.events().unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn rewinding_discards_everything_from_the_tick_onwards() {
+    let path = scratch("rewound");
+    let mut journal = FileJournal::open(&path).unwrap();
+    journal.append(&two_ticks()).unwrap();
+
+    let discarded = journal.rewind(1).unwrap();
+    assert_eq!(discarded, two_ticks()[2..].to_vec());
+    assert_eq!(journal.events().unwrap(), two_ticks()[..2].to_vec());
+    // The journal carries on from the end of the tick it was rewound to
+    journal.append(&[ColonyEvent::TickFinished(1)]).unwrap();
+    let reopened = FileJournal::open(&path).unwrap();
+    assert_eq!(
+        reopened.events().unwrap(),
+        vec![
+            left(0, "alice"),
+            ColonyEvent::TickFinished(0),
+            ColonyEvent::TickFinished(1),
+        ]
+    );
+
+    assert_eq!(journal.rewind(0).unwrap().len(), 3);
+    assert!(journal.events().unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn journals_can_not_be_rewound_past_their_end() {
+    let path = scratch("short");
+    let mut journal = FileJournal::open(&path).unwrap();
+    journal.append(&two_ticks()).unwrap();
+
+    let e = journal.rewind(3).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(journal.events().unwrap(), two_ticks());
+    let _ = std::fs::remove_file(&path);
+}
@@ -1,4 +1,5 @@
 use crate::command::ColonyCommand;
+use crate::view::GameStateColonyView;
 use serde::{Deserialize, Serialize};
 
 pub const OP_PLAYER_TICK: &str = "PlayerTick";
@@ -19,8 +20,3 @@ pub struct PlayerTarget {
     pub player_id: String,
     pub actor_key: String,
 }
-
-#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
-pub struct GameStateColonyView {
-    pub placeholder: u64,
-}
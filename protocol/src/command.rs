@@ -1,9 +1,42 @@
 use serde::{Deserialize, Serialize};
 
-use crate::UnitType;
+use crate::{Location, UnitType};
 
+/// A command issued by a colony. Every command carries the tick during which it was issued
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 pub enum ColonyCommand {
     Pass(u64),
     ConstructUnit(u64, UnitType),
+    /// Moves the ore stockpiled at one of the colony's mines, given by id, into its inventory
+    Collect(u64, u64),
+    /// Steers one of the colony's mobile units, given by id, setting its velocity across the
+    /// surface to `(x, y)` meters per tick
+    Move(u64, u64, (f32, f32)),
+    /// Launches one of the colony's mobile units, given by id, towards a location on
+    /// another satellite
+    Launch(u64, u64, Location),
+    /// Fires one of the colony's weapons, given by id, at another colony's structure, given
+    /// by id
+    Attack(u64, u64, u64),
+    /// Repairs one of the colony's structures, given by id, restoring as much of its missing
+    /// HP as the colony can pay for over the coming ticks
+    Repair(u64, u64),
+    /// Upgrades one of the colony's structures, given by id, to its next level
+    Upgrade(u64, u64),
+}
+
+impl ColonyCommand {
+    /// The tick during which this command was issued
+    pub fn tick(&self) -> u64 {
+        match self {
+            ColonyCommand::Pass(tick) => *tick,
+            ColonyCommand::ConstructUnit(tick, _) => *tick,
+            ColonyCommand::Collect(tick, _) => *tick,
+            ColonyCommand::Move(tick, _, _) => *tick,
+            ColonyCommand::Launch(tick, _, _) => *tick,
+            ColonyCommand::Attack(tick, _, _) => *tick,
+            ColonyCommand::Repair(tick, _) => *tick,
+            ColonyCommand::Upgrade(tick, _) => *tick,
+        }
+    }
 }
@@ -1,28 +1,40 @@
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io::Cursor;
 
 mod actor;
 mod command;
+mod membership;
+mod unit;
+mod view;
 
 pub use actor::*;
 pub use command::*;
+pub use membership::*;
+pub use unit::*;
+pub use view::*;
 
-#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
-pub enum UnitType {
-    None,
-    Mine(OreType),
-}
+/// A type of ore, by name. Which ores exist, and what they're like, is up to the ore catalogue
+/// in each shard's game parameters
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize, Clone, Default)]
+#[serde(transparent)]
+pub struct OreType(pub String);
+
+impl OreType {
+    pub fn new(name: impl Into<String>) -> OreType {
+        OreType(name.into())
+    }
 
-impl Default for UnitType {
-    fn default() -> UnitType {
-        UnitType::None
+    pub fn name(&self) -> &str {
+        &self.0
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
-pub enum OreType {
-    Wasmium,
+impl fmt::Display for OreType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 pub fn serialize<T>(
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::view::Location;
+
+/// A request, sent to a shard over the lattice, for a player to join or leave its game. Each
+/// request carries a nonce, and a shard accepts a given nonce only once
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub enum MembershipRequest {
+    /// Joins the game with the colony whose signed claims token is given. The player's base
+    /// is placed at `start` if that location is free, otherwise wherever the shard finds room
+    Join {
+        player_id: String,
+        colony_jwt: String,
+        start: Option<Location>,
+        nonce: String,
+    },
+    /// Leaves the game, removing everything the player owns from the world. Only the colony
+    /// the player joined with, whose actor key is given, can take them out of the game
+    Leave {
+        player_id: String,
+        actor_key: String,
+        nonce: String,
+    },
+}
+
+/// A [`MembershipRequest`](MembershipRequest) along with proof that it was sent by the colony
+/// it's for: the serialized request, signed with the colony's module key
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SignedMembershipRequest {
+    #[serde(with = "serde_bytes")]
+    pub request: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct MembershipResponse {
+    pub accepted: bool,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,77 @@
+//! The units colonies can build
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::OreType;
+
+/// A unit to be built, with the details that set it apart from other units of its kind
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub enum UnitType {
+    None,
+    Mine(OreType),
+    /// A light, quick-firing weapon with a short range
+    Laser,
+    /// A heavy, slow-firing weapon with a long range
+    Cannon,
+}
+
+impl Default for UnitType {
+    fn default() -> UnitType {
+        UnitType::None
+    }
+}
+
+impl UnitType {
+    /// The kind of unit this is. `UnitType::None` isn't a unit, so it has no kind
+    pub fn kind(&self) -> Option<UnitKind> {
+        match self {
+            UnitType::None => None,
+            UnitType::Mine(_) => Some(UnitKind::Mine),
+            UnitType::Laser => Some(UnitKind::Laser),
+            UnitType::Cannon => Some(UnitKind::Cannon),
+        }
+    }
+}
+
+/// Every kind of unit there is. Game parameters, e.g. how long each unit takes to build,
+/// are given per kind, so kinds serialize as plain names and can be used as map keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum UnitKind {
+    Mine,
+    Laser,
+    Cannon,
+}
+
+impl UnitKind {
+    /// The whole catalogue of unit kinds
+    pub const ALL: &'static [UnitKind] = &[UnitKind::Mine, UnitKind::Laser, UnitKind::Cannon];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitKind::Mine => "Mine",
+            UnitKind::Laser => "Laser",
+            UnitKind::Cannon => "Cannon",
+        }
+    }
+}
+
+impl fmt::Display for UnitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for UnitKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<UnitKind, String> {
+        UnitKind::ALL
+            .iter()
+            .find(|k| k.name() == s)
+            .copied()
+            .ok_or_else(|| format!("{} isn't a kind of unit", s))
+    }
+}
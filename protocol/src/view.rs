@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{OreType, UnitKind};
+
+/// A colony's view of the game world, as of the beginning of a tick
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct GameStateColonyView {
+    pub tick: u64,
+    pub structures: Vec<StructureView>,
+    pub construction_sites: Vec<ConstructionSiteView>,
+    pub mines: Vec<MineView>,
+    /// Untapped deposits on the satellites where the colony has a presence
+    pub deposits: Vec<DepositView>,
+    /// The colony's mobile units
+    #[serde(default)]
+    pub mobiles: Vec<MobileView>,
+    /// The colony's mobile units that are travelling between satellites
+    #[serde(default)]
+    pub transits: Vec<TransitView>,
+    /// The colony's weapons, including those still under construction
+    #[serde(default)]
+    pub weapons: Vec<WeaponView>,
+    /// Other colonies' structures on the satellites where the colony has a presence
+    #[serde(default)]
+    pub enemy_structures: Vec<EnemyStructureView>,
+    /// Attacks made by or on the colony during the previous tick
+    #[serde(default)]
+    pub combat: Vec<CombatReport>,
+    /// Every solar system in the universe
+    #[serde(default)]
+    pub systems: Vec<SolarSystemView>,
+    /// Every satellite in the universe
+    #[serde(default)]
+    pub satellites: Vec<SatelliteView>,
+    /// Every colony's score, highest first
+    #[serde(default)]
+    pub scores: Vec<PlayerScore>,
+    /// Ore the colony has collected and not yet spent
+    #[serde(default)]
+    pub inventory: HashMap<OreType, u32>,
+}
+
+/// A location within the universe: a point on the surface of a satellite within a solar system
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct Location {
+    pub sys: u8,
+    pub sat: u8,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct StructureView {
+    pub id: u64,
+    pub location: Location,
+    pub hp: u16,
+    pub max_hp: u16,
+    #[serde(default)]
+    pub level: u8,
+    /// Armor rating
+    #[serde(default)]
+    pub ar: u8,
+    /// HP paid for and yet to be restored
+    #[serde(default)]
+    pub repairing: u16,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct EnemyStructureView {
+    pub id: u64,
+    /// The player the structure belongs to
+    pub player_id: String,
+    pub location: Location,
+    pub hp: u16,
+    pub max_hp: u16,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct ConstructionSiteView {
+    pub id: u64,
+    pub location: Option<Location>,
+    /// Percentage of completion, from 0 to 100
+    pub progress: u8,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct MineView {
+    pub id: u64,
+    pub location: Option<Location>,
+    pub ore: OreType,
+    /// Amount of ore available for pickup from the mine
+    pub current_qty: u32,
+    pub max_qty: u32,
+    /// Amount of ore remaining in the underlying deposit
+    pub deposit_qty: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct DepositView {
+    pub location: Location,
+    pub ore: OreType,
+    pub qty: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct MobileView {
+    pub id: u64,
+    pub location: Location,
+    /// Meters travelled along each axis every tick
+    pub velocity: (f32, f32),
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TransitView {
+    pub id: u64,
+    /// Where the unit launched from
+    pub origin: Location,
+    /// Where the unit will land
+    pub destination: Location,
+    /// Ticks left until the unit lands
+    pub arrives_in: u64,
+}
+
+/// A star, placed on the universe's star map. Coordinates are in light years
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct SolarSystemView {
+    pub index: u8,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A playable surface within a solar system. Locations on the satellite lie within
+/// `0..width` and `0..height`
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct SatelliteView {
+    pub sys: u8,
+    pub sat: u8,
+    /// Distance from the system's star in astronomical units
+    pub orbit: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct WeaponView {
+    pub id: u64,
+    pub kind: UnitKind,
+    /// Where the weapon is, unless it's in transit
+    pub location: Option<Location>,
+    /// Whether construction of the weapon is complete
+    pub ready: bool,
+    /// Ticks until the weapon can fire again
+    pub reload: u64,
+}
+
+/// An attack by one colony's weapon on another colony's structure
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct CombatReport {
+    pub tick: u64,
+    /// The player whose weapon fired
+    pub attacker: String,
+    /// The player whose structure was hit
+    pub defender: String,
+    pub weapon_id: u64,
+    pub structure_id: u64,
+    /// Where the structure was hit
+    pub location: Location,
+    pub damage: u16,
+    /// Whether the structure was destroyed
+    pub destroyed: bool,
+}
+
+/// A player's score, as of the end of the previous tick
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct PlayerScore {
+    pub player_id: String,
+    pub score: u64,
+}
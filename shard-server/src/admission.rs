@@ -0,0 +1,98 @@
+//! Verification of colony modules before their players are admitted to a shard
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use wascap::jwt::{validate_token, Actor, Claims};
+
+/// Every colony module must carry this tag (or capability) in its signed claims
+pub const COLONY_TAG: &str = "wasmcolonies colony";
+
+/// Whose signatures a shard trusts. An empty list of issuers trusts any issuer, as long as
+/// the module is properly signed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AdmissionPolicy {
+    #[serde(default)]
+    pub trusted_issuers: Vec<String>,
+}
+
+/// The reasons a colony can be refused admission to a shard
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdmissionError {
+    /// The module has no embedded claims
+    Unsigned,
+    /// The claims couldn't be decoded, or don't match the module they're embedded in
+    InvalidClaims(String),
+    /// The claims' signature doesn't check out
+    BadSignature,
+    /// The claims were signed by an issuer the shard doesn't trust
+    UntrustedIssuer(String),
+    Expired(String),
+    NotYetValid(String),
+    /// The module isn't tagged as a colony
+    NotAColony,
+}
+
+impl fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdmissionError::Unsigned => write!(f, "module is not signed"),
+            AdmissionError::InvalidClaims(e) => write!(f, "invalid claims: {}", e),
+            AdmissionError::BadSignature => write!(f, "claims signature is invalid"),
+            AdmissionError::UntrustedIssuer(i) => write!(f, "issuer {} is not trusted", i),
+            AdmissionError::Expired(when) => write!(f, "claims expired {}", when),
+            AdmissionError::NotYetValid(when) => write!(f, "claims are not valid until {}", when),
+            AdmissionError::NotAColony => {
+                write!(f, "module is not tagged as a \"{}\"", COLONY_TAG)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AdmissionError {}
+
+/// Verifies the claims embedded in a colony module, returning them if the colony may be admitted
+pub fn verify_module(
+    policy: &AdmissionPolicy,
+    module: &[u8],
+) -> Result<Claims<Actor>, AdmissionError> {
+    let token = wascap::wasm::extract_claims(module)
+        .map_err(|e| AdmissionError::InvalidClaims(format!("{}", e)))?
+        .ok_or(AdmissionError::Unsigned)?;
+    verify_token(policy, &token.jwt)
+}
+
+/// Verifies a colony's claims token, returning the decoded claims if the colony may be admitted
+pub fn verify_token(policy: &AdmissionPolicy, jwt: &str) -> Result<Claims<Actor>, AdmissionError> {
+    let validation = validate_token::<Actor>(jwt)
+        .map_err(|e| AdmissionError::InvalidClaims(format!("{}", e)))?;
+    if !validation.signature_valid {
+        return Err(AdmissionError::BadSignature);
+    }
+    if validation.expired {
+        return Err(AdmissionError::Expired(validation.expires_human));
+    }
+    if validation.cannot_use_yet {
+        return Err(AdmissionError::NotYetValid(validation.not_before_human));
+    }
+
+    let claims = Claims::<Actor>::decode(jwt)
+        .map_err(|e| AdmissionError::InvalidClaims(format!("{}", e)))?;
+    if !policy.trusted_issuers.is_empty() && !policy.trusted_issuers.contains(&claims.issuer) {
+        return Err(AdmissionError::UntrustedIssuer(claims.issuer));
+    }
+    let is_colony = claims
+        .metadata
+        .as_ref()
+        .map(|actor| {
+            let tagged = actor.tags.iter().flatten().any(|t| t == COLONY_TAG);
+            let capable = actor.caps.iter().flatten().any(|c| c == COLONY_TAG);
+            tagged || capable
+        })
+        .unwrap_or(false);
+    if !is_colony {
+        return Err(AdmissionError::NotAColony);
+    }
+
+    Ok(claims)
+}
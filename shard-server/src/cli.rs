@@ -0,0 +1,257 @@
+//! Command line interface and configuration file for the shard server
+
+use std::path::{Path, PathBuf};
+
+use crate::admission::AdmissionPolicy;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "wcshard", about = "Runs a wasmColonies shard")]
+pub struct Cli {
+    /// TOML configuration file. Flags given on the command line override its settings
+    #[structopt(short, long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub cmd: Command,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Runs the shard with the given colonies
+    Run(RunArgs),
+    /// Checks a game parameters file for problems without running the shard
+    ValidateParams(ParamsArgs),
+    /// Generates the universe described by the game parameters and prints it as JSON
+    GenUniverse(ParamsArgs),
+    /// Rewinds the shard's journal to the start of a tick and runs the shard from there.
+    /// Journaled events from that tick onwards are moved to a separate file
+    Restore(RestoreArgs),
+    /// Plays back a recorded match without invoking any colonies, checking that every tick
+    /// turns out as it did when it was recorded
+    Replay(ReplayArgs),
+}
+
+/// Flags shared by every subcommand that loads game parameters
+#[derive(StructOpt, Debug, Default)]
+pub struct ParamsArgs {
+    /// Game parameters file
+    #[structopt(short, long, parse(from_os_str))]
+    pub params: Option<PathBuf>,
+
+    /// Universe seed, overriding the one in the game parameters
+    #[structopt(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(StructOpt, Debug, Default)]
+pub struct RunArgs {
+    #[structopt(flatten)]
+    pub params: ParamsArgs,
+
+    /// Invoke colonies running in wasmcloud hosts on a NATS lattice instead of running them
+    /// in the embedded host
+    #[structopt(long, overrides_with = "no-lattice")]
+    pub lattice: bool,
+
+    /// Run colonies in the embedded host, even if the config file puts the shard on a lattice
+    #[structopt(long, overrides_with = "lattice")]
+    pub no_lattice: bool,
+
+    /// NATS server the lattice is reached through
+    #[structopt(long)]
+    pub nats_url: Option<String>,
+
+    /// Lattice namespace prefix
+    #[structopt(long)]
+    pub lattice_prefix: Option<String>,
+
+    /// Ticks per second
+    #[structopt(long)]
+    pub tick_rate: Option<f64>,
+
+    /// Public key of an issuer whose colonies may join. May be given more than once, and
+    /// replaces the config file's trusted issuers
+    #[structopt(long = "trusted-issuer", number_of_values = 1)]
+    pub trusted_issuers: Vec<String>,
+
+    /// JSON or YAML file listing the players who start in the game
+    #[structopt(short, long, parse(from_os_str))]
+    pub roster: Option<PathBuf>,
+
+    /// Journal file the shard's events are appended to. If it already has events, the shard
+    /// resumes from where they leave off
+    #[structopt(short, long, parse(from_os_str))]
+    pub journal: Option<PathBuf>,
+
+    /// Directory snapshots of the world are saved in and restored from
+    #[structopt(long, parse(from_os_str))]
+    pub snapshots: Option<PathBuf>,
+
+    /// Ticks between snapshots
+    #[structopt(long)]
+    pub snapshot_interval: Option<u64>,
+
+    /// Record the match, including everything sent to and received from colonies, to this file
+    #[structopt(long, parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    /// Write the results of the match to this file once it ends
+    #[structopt(long, parse(from_os_str))]
+    pub results: Option<PathBuf>,
+
+    /// Signed colony modules, one player per module, in addition to the roster
+    #[structopt(parse(from_os_str))]
+    pub colonies: Vec<PathBuf>,
+}
+
+impl RunArgs {
+    /// Whether the shard should be on a lattice, if either `--lattice` or `--no-lattice` was
+    /// given. Whichever comes last wins
+    pub fn lattice(&self) -> Option<bool> {
+        if self.lattice {
+            Some(true)
+        } else if self.no_lattice {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Default)]
+pub struct RestoreArgs {
+    /// The tick to resume at
+    #[structopt(long)]
+    pub tick: u64,
+
+    #[structopt(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(StructOpt, Debug, Default)]
+pub struct ReplayArgs {
+    /// Match file written by `run --record`
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+/// Shard settings, read from the configuration file and then overridden by command line flags
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ShardConfig {
+    pub params: PathBuf,
+    pub seed: Option<u64>,
+    pub lattice: bool,
+    pub nats_url: String,
+    pub lattice_prefix: Option<String>,
+    /// Ticks per second
+    pub tick_rate: f64,
+    pub admission: AdmissionPolicy,
+    /// JSON or YAML file listing the players who start in the game
+    pub roster: Option<PathBuf>,
+    /// Journal file the shard's events are appended to, and resumed from
+    pub journal: Option<PathBuf>,
+    /// Directory snapshots of the world are saved in and restored from
+    pub snapshots: Option<PathBuf>,
+    /// Ticks between snapshots
+    pub snapshot_interval: u64,
+    /// Match file to record the match to
+    pub record: Option<PathBuf>,
+    /// File the results of the match are written to once it ends
+    pub results: Option<PathBuf>,
+    /// Signed colony modules, one player per module, in addition to the roster
+    pub colonies: Vec<PathBuf>,
+}
+
+impl Default for ShardConfig {
+    fn default() -> ShardConfig {
+        ShardConfig {
+            params: PathBuf::from("./default_params.json"),
+            seed: None,
+            lattice: false,
+            nats_url: "127.0.0.1".to_string(),
+            lattice_prefix: None,
+            tick_rate: 1.0,
+            admission: AdmissionPolicy::default(),
+            roster: None,
+            journal: None,
+            snapshots: None,
+            snapshot_interval: 100,
+            record: None,
+            results: None,
+            colonies: Vec::new(),
+        }
+    }
+}
+
+impl ShardConfig {
+    /// Reads the configuration file if one was given, otherwise starts from the defaults
+    pub fn load(path: Option<&Path>) -> Result<ShardConfig> {
+        match path {
+            Some(path) => {
+                let raw = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Couldn't read config {}: {}", path.display(), e))?;
+                Ok(toml::from_str(&raw)
+                    .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?)
+            }
+            None => Ok(ShardConfig::default()),
+        }
+    }
+
+    pub fn apply_params_args(&mut self, args: ParamsArgs) {
+        if let Some(params) = args.params {
+            self.params = params;
+        }
+        if args.seed.is_some() {
+            self.seed = args.seed;
+        }
+    }
+
+    pub fn apply_run_args(&mut self, args: RunArgs) {
+        if let Some(lattice) = args.lattice() {
+            self.lattice = lattice;
+        }
+        self.apply_params_args(args.params);
+        if let Some(url) = args.nats_url {
+            self.nats_url = url;
+        }
+        if args.lattice_prefix.is_some() {
+            self.lattice_prefix = args.lattice_prefix;
+        }
+        if let Some(rate) = args.tick_rate {
+            self.tick_rate = rate;
+        }
+        if !args.trusted_issuers.is_empty() {
+            self.admission.trusted_issuers = args.trusted_issuers;
+        }
+        if args.roster.is_some() {
+            self.roster = args.roster;
+        }
+        if args.journal.is_some() {
+            self.journal = args.journal;
+        }
+        if args.snapshots.is_some() {
+            self.snapshots = args.snapshots;
+        }
+        if let Some(interval) = args.snapshot_interval {
+            self.snapshot_interval = interval;
+        }
+        if args.record.is_some() {
+            self.record = args.record;
+        }
+        if args.results.is_some() {
+            self.results = args.results;
+        }
+        if !args.colonies.is_empty() {
+            self.colonies = args.colonies;
+        }
+    }
+
+    /// Seconds between the start of one tick and the start of the next
+    pub fn tick_step(&self) -> f64 {
+        1.0 / self.tick_rate
+    }
+}
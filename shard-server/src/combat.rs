@@ -0,0 +1,92 @@
+//! Weapons, and the damage they do to other colonies' structures
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::{CombatReport, Location, UnitKind, WeaponView};
+
+use crate::construction::ConstructionSite;
+use crate::core::{Position, UnitId, Velocity};
+
+/// A colony's weapon. Weapons are built at their owner's base and, once complete, are mobile
+/// units that can be steered and launched like any other
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Weapon {
+    kind: UnitKind,
+    /// Ticks until the weapon can fire again
+    reload: u64,
+}
+
+impl Weapon {
+    pub fn new(kind: UnitKind) -> Weapon {
+        Weapon { kind, reload: 0 }
+    }
+
+    pub fn kind(&self) -> UnitKind {
+        self.kind
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.reload == 0
+    }
+
+    /// Fires the weapon, which can't fire again for `reload_ticks` ticks
+    pub fn fire(&mut self, reload_ticks: u64) {
+        self.reload = reload_ticks;
+    }
+
+    /// How this weapon appears in its owner's view of the game state
+    pub fn view(&self, id: u64, location: Option<Location>, ready: bool) -> WeaponView {
+        WeaponView {
+            id,
+            kind: self.kind,
+            location,
+            ready,
+            reload: self.reload,
+        }
+    }
+}
+
+/// The attacks made during the tick last played, for the colonies on both sides of each
+/// attack to see in their next view of the game
+#[derive(Debug, Default)]
+pub struct CombatReports(pub Vec<CombatReport>);
+
+/// Starts construction of a weapon belonging to the given player, at the given position,
+/// to be completed in `duration` ticks. The weapon keeps its id once it's armed
+pub fn begin_weapon(
+    commands: &mut Commands,
+    owner: Entity,
+    id: UnitId,
+    kind: UnitKind,
+    position: &Position,
+    duration: u64,
+) {
+    let weapon = commands
+        .spawn_bundle((
+            id,
+            Weapon::new(kind),
+            ConstructionSite::new(kind, duration),
+            position.clone(),
+        ))
+        .id();
+    commands.entity(owner).push_children(&[weapon]);
+}
+
+/// Puts weapons whose construction has completed into service as mobile units
+pub fn arm(mut commands: Commands, query: Query<(Entity, &ConstructionSite), With<Weapon>>) {
+    for (entity, site) in query.iter() {
+        if site.is_complete() {
+            commands
+                .entity(entity)
+                .remove::<ConstructionSite>()
+                .insert(Velocity::default());
+        }
+    }
+}
+
+/// Brings every weapon that has fired a tick closer to being able to fire again
+pub fn reload(mut query: Query<&mut Weapon>) {
+    for mut weapon in query.iter_mut() {
+        weapon.reload = weapon.reload.saturating_sub(1);
+    }
+}
@@ -0,0 +1,716 @@
+//! Application of the commands returned by colonies to the game world
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use wasmcolonies_domain::{ColonyEvent, ConstructionRules};
+use wasmcolonies_protocol::{ColonyCommand, CombatReport, Location, OreType, UnitKind, UnitType};
+
+use crate::combat::{begin_weapon, CombatReports, Weapon};
+use crate::construction::ConstructionSite;
+use crate::core::{Position, UnitId, UnitIds, Velocity};
+use crate::movement::MAX_SPEED;
+use crate::player::Player;
+use crate::procgen::Deposit;
+use crate::resources::{Inventory, Mine, MINE_MAX_QTY};
+use crate::rules::GameParameters;
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::travel::{launch, StarMap};
+
+/// The list of commands a player's colony returned during the actor RPC stage
+#[derive(Debug, Clone)]
+pub struct ColonyCommands {
+    pub player: Entity,
+    pub commands: Vec<ColonyCommand>,
+}
+
+/// The player whose command is being applied, during the tick it was issued for
+struct Issuer<'a> {
+    tick: u64,
+    entity: Entity,
+    player: &'a Player,
+    inventory: Mut<'a, Inventory>,
+}
+
+/// Applies each colony's commands to the world. Commands that can't be carried out (e.g. there's
+/// no deposit left to build a mine on, or the player can't afford the mine) or that weren't
+/// issued for the current tick are logged and otherwise ignored. Attacks made during the tick
+/// replace those of the previous tick in the combat reports
+#[allow(clippy::too_many_arguments)]
+pub fn apply_colony_commands(
+    mut commands: Commands,
+    tick: Res<GameTick>,
+    params: Res<GameParameters>,
+    rules: Res<ConstructionRules>,
+    mut received: EventReader<ColonyCommands>,
+    mut players: Query<(&Player, &mut Inventory)>,
+    owners: Query<&Player>,
+    mut structures: Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    deposits: Query<(Entity, &Deposit, &Position)>,
+    mut mines: Query<(&mut Mine, &Position, &Parent, &UnitId)>,
+    mut mobiles: Query<(Entity, &mut Velocity, &Position, &Parent, &UnitId)>,
+    mut weapons: Query<
+        (&mut Weapon, Option<&Position>, &Parent, &UnitId),
+        Without<ConstructionSite>,
+    >,
+    star_map: StarMap,
+    mut unit_ids: ResMut<UnitIds>,
+    mut reports: ResMut<CombatReports>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    reports.0.clear();
+    // Deposits can only be claimed by one construction site, even within the same tick
+    let mut claimed = HashSet::new();
+    // Units that have left their satellite this tick can't be launched or steered again
+    let mut launched = HashSet::new();
+
+    for ColonyCommands {
+        player: player_entity,
+        commands: cmds,
+    } in received.iter()
+    {
+        let (player, inventory) = match players.get_mut(*player_entity) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        let mut issuer = Issuer {
+            tick: tick.0,
+            entity: *player_entity,
+            player,
+            inventory,
+        };
+        for cmd in cmds {
+            if cmd.tick() != tick.0 {
+                warn!(
+                    "Player {} issued a command for tick {} during tick {}, ignoring",
+                    player.id,
+                    cmd.tick(),
+                    tick.0
+                );
+                continue;
+            }
+            match cmd {
+                ColonyCommand::Pass(_) => {}
+                ColonyCommand::ConstructUnit(_, utype @ UnitType::Mine(ore)) => construct_mine(
+                    &mut commands,
+                    &mut issuer,
+                    utype,
+                    ore,
+                    &params,
+                    &rules,
+                    &mut structures,
+                    &deposits,
+                    &mut claimed,
+                    &mut unit_ids,
+                    &mut events,
+                ),
+                ColonyCommand::ConstructUnit(_, UnitType::None) => {
+                    debug!("Player {} asked to construct nothing", player.id);
+                }
+                ColonyCommand::ConstructUnit(_, utype) => construct_weapon(
+                    &mut commands,
+                    &mut issuer,
+                    utype,
+                    &params,
+                    &rules,
+                    &mut structures,
+                    &mut unit_ids,
+                    &mut events,
+                ),
+                ColonyCommand::Collect(_, mine_id) => {
+                    collect(&mut issuer, *mine_id, &mut mines, &mut events)
+                }
+                ColonyCommand::Move(_, mobile_id, velocity) => steer(
+                    &mut issuer,
+                    *mobile_id,
+                    *velocity,
+                    &mut mobiles,
+                    &launched,
+                    &mut events,
+                ),
+                ColonyCommand::Launch(_, mobile_id, destination) => launch_unit(
+                    &mut commands,
+                    &mut issuer,
+                    *mobile_id,
+                    destination,
+                    &params,
+                    &mut mobiles,
+                    &star_map,
+                    &mut launched,
+                    &mut events,
+                ),
+                ColonyCommand::Attack(_, weapon_id, structure_id) => attack(
+                    &mut commands,
+                    &mut issuer,
+                    *weapon_id,
+                    *structure_id,
+                    &params,
+                    &mut weapons,
+                    &mut structures,
+                    &owners,
+                    &mut reports,
+                    &mut events,
+                ),
+                ColonyCommand::Repair(_, structure_id) => repair(
+                    &mut issuer,
+                    *structure_id,
+                    &params,
+                    &mut structures,
+                    &mut events,
+                ),
+                ColonyCommand::Upgrade(_, structure_id) => upgrade(
+                    &mut issuer,
+                    *structure_id,
+                    &params,
+                    &mut structures,
+                    &mut events,
+                ),
+            }
+        }
+    }
+}
+
+/// Begins construction of a mine on the unclaimed deposit of the given ore nearest the
+/// player's base
+#[allow(clippy::too_many_arguments)]
+fn construct_mine(
+    commands: &mut Commands,
+    issuer: &mut Issuer,
+    utype: &UnitType,
+    ore: &OreType,
+    params: &GameParameters,
+    rules: &ConstructionRules,
+    structures: &mut Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    deposits: &Query<(Entity, &Deposit, &Position)>,
+    claimed: &mut HashSet<Entity>,
+    unit_ids: &mut UnitIds,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let rule = match rules.build_rule(utype) {
+        Some(rule) => rule,
+        None => {
+            warn!(
+                "Player {} can't build {:?}, the game parameters have no rule for it",
+                player.id, utype
+            );
+            return;
+        }
+    };
+    let spec = match params.ore(ore) {
+        Some(spec) => spec,
+        None => {
+            warn!(
+                "Player {} asked for a mine of {}, which isn't in the ore catalogue",
+                player.id, ore
+            );
+            return;
+        }
+    };
+    let origin = match structures
+        .iter_mut()
+        .find(|(_, _, _, p, _)| p.0 == issuer.entity)
+    {
+        Some((_, _, pos, _, _)) => pos,
+        None => {
+            warn!("Player {} has no structures to build from", player.id);
+            return;
+        }
+    };
+    let nearest = deposits
+        .iter()
+        .filter(|(e, d, p)| {
+            !claimed.contains(e) && d.ore == *ore && p.sys == origin.sys && p.sat == origin.sat
+        })
+        .min_by(|(_, _, a), (_, _, b)| {
+            a.distance(origin)
+                .partial_cmp(&b.distance(origin))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    let (deposit_entity, deposit, position) = match nearest {
+        Some(d) => d,
+        None => {
+            warn!(
+                "Player {} has no {} deposit available for a mine",
+                player.id, ore
+            );
+            return;
+        }
+    };
+    if !issuer.inventory.spend(&rule.cost) {
+        warn!(
+            "Player {} can't afford a mine, it costs {:?}",
+            player.id, rule.cost
+        );
+        return;
+    }
+    claimed.insert(deposit_entity);
+    let id = unit_ids.issue();
+    begin_mine(
+        commands,
+        issuer.entity,
+        id,
+        deposit_entity,
+        deposit,
+        position,
+        rule.ticks,
+        spec.yield_rate,
+    );
+    events.send(ColonyEvent::UnitConstructionBegan {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        utype: utype.clone(),
+        location: position.into(),
+        yield_in: rule.ticks,
+        cost: rule.cost.clone(),
+        unit_id: Some(id.0),
+    });
+    info!("Player {} began construction of a mine", player.id);
+}
+
+/// Begins construction of a weapon at the player's base
+#[allow(clippy::too_many_arguments)]
+fn construct_weapon(
+    commands: &mut Commands,
+    issuer: &mut Issuer,
+    utype: &UnitType,
+    params: &GameParameters,
+    rules: &ConstructionRules,
+    structures: &mut Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    unit_ids: &mut UnitIds,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let rule = match rules.build_rule(utype) {
+        Some(rule) => rule,
+        None => {
+            warn!(
+                "Player {} can't build {:?}, the game parameters have no rule for it",
+                player.id, utype
+            );
+            return;
+        }
+    };
+    let kind = match utype.kind() {
+        Some(kind) if params.weapon(kind).is_some() => kind,
+        _ => {
+            warn!(
+                "Player {} can't build {:?}, it isn't in the weapons",
+                player.id, utype
+            );
+            return;
+        }
+    };
+    let origin = match structures
+        .iter_mut()
+        .find(|(_, _, _, p, _)| p.0 == issuer.entity)
+    {
+        Some((_, _, pos, _, _)) => pos.clone(),
+        None => {
+            warn!("Player {} has no structures to build from", player.id);
+            return;
+        }
+    };
+    if !issuer.inventory.spend(&rule.cost) {
+        warn!(
+            "Player {} can't afford a {}, it costs {:?}",
+            player.id, kind, rule.cost
+        );
+        return;
+    }
+    let id = unit_ids.issue();
+    begin_weapon(commands, issuer.entity, id, kind, &origin, rule.ticks);
+    events.send(ColonyEvent::UnitConstructionBegan {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        utype: utype.clone(),
+        location: (&origin).into(),
+        yield_in: rule.ticks,
+        cost: rule.cost.clone(),
+        unit_id: Some(id.0),
+    });
+    info!("Player {} began construction of a {}", player.id, kind);
+}
+
+/// Moves the ore stockpiled at one of the player's mines into their inventory
+fn collect(
+    issuer: &mut Issuer,
+    mine_id: u64,
+    mines: &mut Query<(&mut Mine, &Position, &Parent, &UnitId)>,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let (mut mine, position) = match mines.iter_mut().find(|(_, _, _, id)| id.0 == mine_id) {
+        Some((mine, position, parent, _)) if parent.0 == issuer.entity => (mine, position),
+        _ => {
+            warn!(
+                "Player {} has no mine {} to collect from",
+                player.id, mine_id
+            );
+            return;
+        }
+    };
+    let qty = mine.collect();
+    if qty == 0 {
+        return;
+    }
+    issuer.inventory.add(mine.ore().clone(), qty);
+    events.send(ColonyEvent::OreCollected {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        location: position.into(),
+        ore: mine.ore().clone(),
+        qty,
+    });
+}
+
+/// Sets the velocity of one of the player's mobile units
+fn steer(
+    issuer: &mut Issuer,
+    mobile_id: u64,
+    (x, y): (f32, f32),
+    mobiles: &mut Query<(Entity, &mut Velocity, &Position, &Parent, &UnitId)>,
+    launched: &HashSet<Entity>,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let (mut velocity, position, id) = match mobiles.iter_mut().find(|(.., id)| id.0 == mobile_id) {
+        Some((unit, velocity, position, parent, id))
+            if parent.0 == issuer.entity && !launched.contains(&unit) =>
+        {
+            (velocity, position, *id)
+        }
+        _ => {
+            warn!(
+                "Player {} has no mobile unit {} to steer",
+                player.id, mobile_id
+            );
+            return;
+        }
+    };
+    let mut steered = Velocity::from((x, y));
+    if !(steered.x.is_finite() && steered.y.is_finite()) {
+        warn!(
+            "Player {} can't steer unit {} to {:?}",
+            player.id,
+            mobile_id,
+            (x, y)
+        );
+        return;
+    }
+    // Units too fast for their own good keep their heading at top speed
+    let speed = steered.speed();
+    if speed > MAX_SPEED {
+        steered.x *= MAX_SPEED / speed;
+        steered.y *= MAX_SPEED / speed;
+    }
+    events.send(ColonyEvent::UnitSteered {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        unit_id: id.0,
+        location: position.into(),
+        velocity: (&steered).into(),
+    });
+    *velocity = steered;
+}
+
+/// Sends one of the player's mobile units to another satellite
+#[allow(clippy::too_many_arguments)]
+fn launch_unit(
+    commands: &mut Commands,
+    issuer: &mut Issuer,
+    mobile_id: u64,
+    destination: &Location,
+    params: &GameParameters,
+    mobiles: &mut Query<(Entity, &mut Velocity, &Position, &Parent, &UnitId)>,
+    star_map: &StarMap,
+    launched: &mut HashSet<Entity>,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let (unit, position, id) = match mobiles.iter_mut().find(|(.., id)| id.0 == mobile_id) {
+        Some((unit, _, position, parent, id))
+            if parent.0 == issuer.entity && !launched.contains(&unit) =>
+        {
+            (unit, position, *id)
+        }
+        _ => {
+            warn!(
+                "Player {} has no mobile unit {} on a satellite to launch",
+                player.id, mobile_id
+            );
+            return;
+        }
+    };
+    let mut landing = Position::from(destination);
+    let surface = match star_map.satellite(landing.sys, landing.sat) {
+        Some(s) if (s.sys, s.sat) != (position.sys, position.sat) => s,
+        _ => {
+            warn!(
+                "Player {} can't launch unit {} to satellite {}/{}",
+                player.id, mobile_id, landing.sys, landing.sat
+            );
+            return;
+        }
+    };
+    if !(landing.x.is_finite() && landing.y.is_finite()) {
+        warn!(
+            "Player {} can't launch unit {} to {:?}",
+            player.id, mobile_id, destination
+        );
+        return;
+    }
+    // Units land as close to where they were sent as the surface allows
+    landing.x = landing.x.clamp(0., surface.width);
+    landing.y = landing.y.clamp(0., surface.height);
+    let ticks = match star_map.travel_ticks(&params.travel, position, &landing) {
+        Some(ticks) => ticks,
+        None => {
+            warn!(
+                "Player {} can't launch unit {}, its course can't be plotted",
+                player.id, mobile_id
+            );
+            return;
+        }
+    };
+    launched.insert(unit);
+    events.send(ColonyEvent::UnitLaunched {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        unit_id: id.0,
+        location: position.into(),
+        destination: (&landing).into(),
+        arrive_in: ticks,
+    });
+    info!(
+        "Player {} launched a unit to {}/{}, landing in {} ticks",
+        player.id, landing.sys, landing.sat, ticks
+    );
+    launch(commands, unit, position, landing, ticks);
+}
+
+/// Fires one of the player's weapons at another player's structure, destroying it if it has
+/// no HP left
+#[allow(clippy::too_many_arguments)]
+fn attack(
+    commands: &mut Commands,
+    issuer: &mut Issuer,
+    weapon_id: u64,
+    structure_id: u64,
+    params: &GameParameters,
+    weapons: &mut Query<
+        (&mut Weapon, Option<&Position>, &Parent, &UnitId),
+        Without<ConstructionSite>,
+    >,
+    structures: &mut Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    owners: &Query<&Player>,
+    reports: &mut CombatReports,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let (mut weapon, position, id) = match weapons.iter_mut().find(|(.., id)| id.0 == weapon_id) {
+        Some((weapon, Some(position), parent, id)) if parent.0 == issuer.entity => {
+            (weapon, position, *id)
+        }
+        _ => {
+            warn!(
+                "Player {} has no weapon {} ready to fire on a satellite",
+                player.id, weapon_id
+            );
+            return;
+        }
+    };
+    let spec = match params.weapon(weapon.kind()) {
+        Some(spec) if weapon.is_loaded() => spec,
+        _ => {
+            warn!("Player {}'s weapon {} isn't loaded", player.id, weapon_id);
+            return;
+        }
+    };
+    let target = structures.iter_mut().find(|(.., id)| id.0 == structure_id);
+    let (target_entity, mut target, target_position, defender) = match target {
+        Some((e, s, p, parent, _))
+            if parent.0 != issuer.entity && s.is_attackable() && !s.is_destroyed() =>
+        {
+            (e, s, p, parent.0)
+        }
+        _ => {
+            warn!(
+                "Player {} has no enemy structure {} to attack",
+                player.id, structure_id
+            );
+            return;
+        }
+    };
+    if (position.sys, position.sat) != (target_position.sys, target_position.sat)
+        || position.distance(target_position) > spec.range
+    {
+        warn!(
+            "Structure {} is out of range of player {}'s weapon {}",
+            structure_id, player.id, weapon_id
+        );
+        return;
+    }
+    let defender = owners
+        .get(defender)
+        .map(|p| p.id.clone())
+        .unwrap_or_default();
+
+    let damage = target.armor(spec.damage);
+    target.damage(damage);
+    weapon.fire(spec.reload_ticks);
+    events.send(ColonyEvent::StructureAttacked {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        unit_id: id.0,
+        target_player_id: defender.clone(),
+        location: position.into(),
+        target: target_position.into(),
+        damage,
+    });
+    if target.is_destroyed() {
+        info!(
+            "Player {} destroyed a structure of player {}",
+            player.id, defender
+        );
+        commands.entity(target_entity).despawn_recursive();
+        events.send(ColonyEvent::StructureDestroyed {
+            tick: issuer.tick,
+            player_id: defender.clone(),
+            destroyed_by: player.id.clone(),
+            location: target_position.into(),
+        });
+    }
+    reports.0.push(CombatReport {
+        tick: issuer.tick,
+        attacker: player.id.clone(),
+        defender,
+        weapon_id,
+        structure_id,
+        location: target_position.into(),
+        damage,
+        destroyed: target.is_destroyed(),
+    });
+}
+
+/// Pays for the repair of one of the player's damaged structures
+fn repair(
+    issuer: &mut Issuer,
+    structure_id: u64,
+    params: &GameParameters,
+    structures: &mut Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let owned = structures.iter_mut().find(|(.., id)| id.0 == structure_id);
+    let (mut structure, position) = match owned {
+        Some((_, s, p, parent, _)) if parent.0 == issuer.entity => (s, p),
+        _ => {
+            warn!("Player {} has no structure {}", player.id, structure_id);
+            return;
+        }
+    };
+    // Repairs are paid for up front, for as much of the damage as the player can afford
+    let affordable = params
+        .repair
+        .cost_per_hp
+        .iter()
+        .filter(|(_, per_hp)| **per_hp > 0)
+        .map(|(ore, per_hp)| issuer.inventory.amount(ore) / per_hp)
+        .min()
+        .unwrap_or(u32::MAX);
+    let hp = (structure.unrepaired() as u32).min(affordable) as u16;
+    if hp == 0 {
+        warn!(
+            "Player {} can't repair structure {}, it's undamaged or they can't afford it",
+            player.id, structure_id
+        );
+        return;
+    }
+    let cost = params.repair.cost(hp);
+    issuer.inventory.spend(&cost);
+    structure.repair(hp);
+    events.send(ColonyEvent::RepairBegan {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        location: position.into(),
+        hp,
+        cost,
+    });
+}
+
+/// Raises one of the player's structures to its next level
+fn upgrade(
+    issuer: &mut Issuer,
+    structure_id: u64,
+    params: &GameParameters,
+    structures: &mut Query<(Entity, &mut Structure, &Position, &Parent, &UnitId)>,
+    events: &mut EventWriter<ColonyEvent>,
+) {
+    let player = issuer.player;
+    let owned = structures.iter_mut().find(|(.., id)| id.0 == structure_id);
+    let (mut structure, position) = match owned {
+        Some((_, s, p, parent, _)) if parent.0 == issuer.entity => (s, p),
+        _ => {
+            warn!("Player {} has no structure {}", player.id, structure_id);
+            return;
+        }
+    };
+    let upgrade = match params.base_upgrade(structure.level()) {
+        Some(upgrade) => upgrade,
+        None => {
+            warn!(
+                "Player {}'s structure {} is already at its highest level",
+                player.id, structure_id
+            );
+            return;
+        }
+    };
+    if !issuer.inventory.spend(&upgrade.cost) {
+        warn!(
+            "Player {} can't afford an upgrade, it costs {:?}",
+            player.id, upgrade.cost
+        );
+        return;
+    }
+    structure.upgrade(upgrade.max_hp, upgrade.ar);
+    events.send(ColonyEvent::StructureUpgraded {
+        tick: issuer.tick,
+        player_id: player.id.clone(),
+        location: position.into(),
+        level: structure.level(),
+        cost: upgrade.cost.clone(),
+    });
+    info!(
+        "Player {} upgraded a structure to level {}",
+        player.id,
+        structure.level()
+    );
+}
+
+/// Turns a deposit into the construction site of a new mine, with the given id, belonging to
+/// the given player, to be completed in `duration` ticks and then extract `yield_rate` ore
+/// every tick
+#[allow(clippy::too_many_arguments)]
+pub fn begin_mine(
+    commands: &mut Commands,
+    owner: Entity,
+    id: UnitId,
+    deposit_entity: Entity,
+    deposit: &Deposit,
+    position: &Position,
+    duration: u64,
+    yield_rate: u32,
+) {
+    commands.entity(deposit_entity).despawn();
+    let mine = commands
+        .spawn_bundle((
+            id,
+            Mine::new(deposit.ore.clone(), MINE_MAX_QTY, deposit.qty, yield_rate),
+            ConstructionSite::new(UnitKind::Mine, duration),
+            position.clone(),
+        ))
+        .id();
+    commands.entity(owner).push_children(&[mine]);
+}
@@ -1,19 +1,73 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::UnitKind;
 
+use crate::core::Position;
+use crate::player::Player;
+use crate::tick::GameTick;
+
+/// A unit being built. Construction completes once the site has been worked on for as many
+/// ticks as the unit's build rule says it takes
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConstructionSite {
-    pub progress: u8,
-    pub progress_rate_pct: u8,
+    /// The kind of unit being built
+    pub kind: UnitKind,
+    /// Ticks spent on construction so far
+    pub elapsed: u64,
+    /// Ticks construction takes in all
+    pub duration: u64,
 }
 
-pub fn construction(mut query: Query<&mut ConstructionSite>) {
-    for mut site in query.iter_mut() {
-        let old = site.progress;
-        site.progress = site
-            .progress
-            .saturating_add(site.progress_rate_pct)
-            .min(100);
-        if old < site.progress && site.progress == 100 {
+impl ConstructionSite {
+    pub fn new(kind: UnitKind, duration: u64) -> ConstructionSite {
+        ConstructionSite {
+            kind,
+            elapsed: 0,
+            duration,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// How far along construction is, as a percentage
+    pub fn progress(&self) -> u8 {
+        match self.duration {
+            0 => 100,
+            d => (self.elapsed.min(d) * 100 / d) as u8,
+        }
+    }
+
+    /// The number of ticks until construction completes
+    pub fn ticks_remaining(&self) -> u64 {
+        self.duration.saturating_sub(self.elapsed)
+    }
+}
+
+/// Works on every construction site, announcing the units completed during the tick
+pub fn construction(
+    tick: Res<GameTick>,
+    mut query: Query<(&mut ConstructionSite, &Position, &Parent)>,
+    players: Query<&Player>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    for (mut site, position, parent) in query.iter_mut() {
+        if site.is_complete() {
+            continue;
+        }
+        site.elapsed += 1;
+        if site.is_complete() {
             info!("Construction site completed.");
+            if let Ok(player) = players.get(parent.0) {
+                events.send(ColonyEvent::UnitConstructed {
+                    tick: tick.0,
+                    player_id: player.id.clone(),
+                    kind: site.kind,
+                    location: position.into(),
+                });
+            }
         }
     }
 }
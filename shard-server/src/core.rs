@@ -2,18 +2,7 @@
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum UnitType {
-    Unknown,
-    Mine,
-}
-
-impl Default for UnitType {
-    fn default() -> UnitType {
-        UnitType::Unknown
-    }
-}
+use wasmcolonies_protocol::Location;
 
 /// Indicates a player's position within a game shard. The game is played on 2D planes,
 /// each of which represents the usable surface of a planet, asteroid, or moon
@@ -33,15 +22,43 @@ impl Position {
     pub fn new(sys: u8, sat: u8, x: f32, y: f32) -> Position {
         Position { sys, sat, x, y }
     }
+
+    /// Straight-line distance between two positions on the same satellite surface
+    pub fn distance(&self, other: &Position) -> f32 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
 }
 
-/// A 2-dimensional vector indicating the velocity of an entity in meters per second.
-#[derive(Clone, Debug, Default)]
+impl From<&Position> for Location {
+    fn from(source: &Position) -> Location {
+        Location {
+            sys: source.sys,
+            sat: source.sat,
+            x: source.x,
+            y: source.y,
+        }
+    }
+}
+
+impl From<&Location> for Position {
+    fn from(source: &Location) -> Position {
+        Position::new(source.sys, source.sat, source.x, source.y)
+    }
+}
+
+/// A 2-dimensional vector indicating the velocity of an entity in meters per tick
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
 }
 
+impl Velocity {
+    pub fn speed(&self) -> f32 {
+        (self.x.powi(2) + self.y.powi(2)).sqrt()
+    }
+}
+
 impl From<(f32, f32)> for Velocity {
     fn from(source: (f32, f32)) -> Velocity {
         Velocity {
@@ -51,8 +68,41 @@ impl From<(f32, f32)> for Velocity {
     }
 }
 
-#[derive(Clone, Debug, Bundle)]
+impl From<&Velocity> for (f32, f32) {
+    fn from(source: &Velocity) -> (f32, f32) {
+        (source.x, source.y)
+    }
+}
+
+/// Identifies a unit for as long as the shard lasts. Entities are renumbered whenever the
+/// world is rebuilt, and units can share a position, so the journal refers to units by this
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct UnitId(pub u64);
+
+/// Hands out unit ids, each only once
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnitIds {
+    next: u64,
+}
+
+impl UnitIds {
+    pub fn issue(&mut self) -> UnitId {
+        self.next += 1;
+        UnitId(self.next)
+    }
+
+    /// Makes sure an id issued elsewhere, e.g. read from the journal, isn't issued again
+    pub fn reserve(&mut self, id: UnitId) {
+        self.next = self.next.max(id.0);
+    }
+}
+
+/// A unit that moves across the surface of its satellite, steered by its owner's colony
+#[derive(Clone, Debug, Default, Bundle)]
 pub struct MobileBundle {
-    position: Position,
-    velocity: Velocity,
+    pub id: UnitId,
+    pub position: Position,
+    pub velocity: Velocity,
 }
@@ -0,0 +1,317 @@
+//! A headless shard for integration tests. It runs the same schedule as the `wcshard` binary,
+//! but each tick is played when the test asks for it rather than when the clock says so, and
+//! colonies are stood in for by scripts
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{mpsc::Sender, Arc, Mutex};
+
+use bevy::prelude::*;
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{ColonyCommand, PlayerTick, PlayerTickResponse};
+
+use crate::combat::Weapon;
+use crate::construction::ConstructionSite;
+use crate::core::{Position, UnitId, Velocity};
+use crate::journal::Journal;
+use crate::lobby::{Lobby, Membership};
+use crate::player::Player;
+use crate::procgen::Deposit;
+use crate::recording::{
+    self, playback_membership, verify_tick, MatchRecorder, Playback, PlaybackOutcome,
+};
+use crate::resources::{Inventory, Mine};
+use crate::rules::{ColoniesStage, GameParameters, WasmColoniesLabels};
+use crate::snapshot::by_position;
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::transport::{ColonyFault, ColonyInvoker, ColonyTransport};
+use crate::travel::Transit;
+use crate::{Result, ShardPlugin};
+
+/// Stands in for a colony, returning its commands for each tick it's sent
+pub type Script = Box<dyn FnMut(&PlayerTick) -> Vec<ColonyCommand> + Send>;
+
+/// Answers ticks on behalf of colonies by running their scripts. A colony without a script
+/// issues no commands
+#[derive(Clone, Default)]
+pub struct ScriptedTransport {
+    scripts: Arc<Mutex<HashMap<String, Script>>>,
+}
+
+impl ScriptedTransport {
+    /// Scripts the colony with the given actor key, replacing any script it already had
+    pub fn script<F>(&self, actor_key: &str, script: F)
+    where
+        F: FnMut(&PlayerTick) -> Vec<ColonyCommand> + Send + 'static,
+    {
+        self.scripts
+            .lock()
+            .unwrap()
+            .insert(actor_key.to_string(), Box::new(script));
+    }
+}
+
+impl ColonyTransport for ScriptedTransport {
+    fn player_tick(
+        &self,
+        actor_key: &str,
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault> {
+        let commands = match self.scripts.lock().unwrap().get_mut(actor_key) {
+            Some(script) => script(&tick),
+            None => Vec::new(),
+        };
+        Ok(PlayerTickResponse { commands })
+    }
+
+    fn release(&self, actor_key: &str) {
+        self.scripts.lock().unwrap().remove(actor_key);
+    }
+}
+
+/// A shard played synchronously, one tick per call to [`advance`](Harness::advance). Nothing
+/// happens until it's first advanced, including the big bang
+pub struct Harness {
+    app: App,
+    colonies: ScriptedTransport,
+    lobby: Sender<Membership>,
+}
+
+impl Harness {
+    /// Builds a shard with the given parameters, no players, and an in-memory journal
+    pub fn new(params: GameParameters) -> Harness {
+        let colonies = ScriptedTransport::default();
+        Harness::build(params, colonies.clone(), colonies, |_| {})
+    }
+
+    /// Builds a shard like [`new`](Harness::new) that also records the match it plays to the
+    /// given file
+    pub fn recording(params: GameParameters, path: &Path) -> Result<Harness> {
+        let recorder = MatchRecorder::create(path, &params)?;
+        let colonies = ScriptedTransport::default();
+        let transport = recorder.transport(Box::new(colonies.clone()));
+        Ok(Harness::build(params, colonies, transport, |app| {
+            app.insert_resource(recorder);
+        }))
+    }
+
+    /// Builds a shard that plays back the match recorded in the given file instead of asking
+    /// colonies for their commands. How the playback turned out is reported through the
+    /// returned outcome, once it diverges or reaches the end of the recording
+    pub fn playback(path: &Path) -> Result<(Harness, Arc<Mutex<Option<PlaybackOutcome>>>)> {
+        let (params, ticks) = recording::load(path)?;
+        let outcome = Arc::new(Mutex::new(None));
+        let (playback, transport) = Playback::new(ticks, outcome.clone());
+        let harness = Harness::build(params, ScriptedTransport::default(), transport, |app| {
+            app.insert_resource(playback)
+                .stage(ColoniesStage::Tick, |tick: &mut Schedule| {
+                    tick.add_system_to_stage(
+                        ColoniesStage::Membership,
+                        playback_membership
+                            .system()
+                            .before(WasmColoniesLabels::Membership),
+                    )
+                    .add_system_to_stage(
+                        ColoniesStage::Journal,
+                        verify_tick.system().after(WasmColoniesLabels::Journal),
+                    )
+                });
+        });
+        Ok((harness, outcome))
+    }
+
+    fn build<T, F>(
+        params: GameParameters,
+        colonies: ScriptedTransport,
+        transport: T,
+        setup: F,
+    ) -> Harness
+    where
+        T: ColonyTransport + 'static,
+        F: FnOnce(&mut AppBuilder),
+    {
+        let lobby = Lobby::default();
+        let sender = lobby.sender();
+        let mut app = App::build();
+        app.insert_resource(params)
+            .insert_resource(ColonyInvoker::new(transport))
+            .insert_resource(lobby)
+            .add_plugins(MinimalPlugins)
+            .add_plugin(ShardPlugin::default());
+        setup(&mut app);
+        Harness {
+            app: app.app,
+            colonies,
+            lobby: sender,
+        }
+    }
+
+    /// The scripts answering for the shard's colonies
+    pub fn colonies(&self) -> &ScriptedTransport {
+        &self.colonies
+    }
+
+    /// Queues a player to join at the start of the next tick, at the given position if it's free
+    pub fn join(&self, player_id: &str, actor_key: &str, start: Option<Position>) {
+        let player = Player {
+            id: player_id.to_string(),
+            actor_key: actor_key.to_string(),
+        };
+        self.request(Membership::Join { player, start });
+    }
+
+    /// Queues a player to leave at the start of the next tick
+    pub fn leave(&self, player_id: &str) {
+        self.request(Membership::Leave {
+            player_id: player_id.to_string(),
+            actor_key: None,
+        });
+    }
+
+    /// Queues a membership change, such as one let in by a [`Gatekeeper`](crate::lobby::Gatekeeper), to be
+    /// applied at the start of the next tick
+    pub fn request(&self, change: Membership) {
+        // The lobby lives as long as the app, so it's always listening
+        let _ = self.lobby.send(change);
+    }
+
+    /// Plays the given number of ticks
+    pub fn advance(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    /// The tick that will be played next
+    pub fn tick(&self) -> u64 {
+        self.app
+            .world
+            .get_resource::<GameTick>()
+            .map(|t| t.0)
+            .unwrap_or(0)
+    }
+
+    /// The shard's world, for anything the harness has no shortcut for
+    pub fn world(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    /// Every event journaled so far
+    pub fn events(&self) -> Vec<ColonyEvent> {
+        self.app
+            .world
+            .get_resource::<Journal>()
+            .and_then(|j| j.0.events().ok())
+            .unwrap_or_default()
+    }
+
+    /// The entity of the player with the given id, if they're in the game
+    pub fn player(&mut self, player_id: &str) -> Option<Entity> {
+        let world = &mut self.app.world;
+        world
+            .query::<(Entity, &Player)>()
+            .iter(world)
+            .find(|(_, p)| p.id == player_id)
+            .map(|(e, _)| e)
+    }
+
+    /// The ore a player holds, if they're in the game
+    pub fn inventory(&mut self, player_id: &str) -> Option<Inventory> {
+        let player = self.player(player_id)?;
+        self.app.world.get::<Inventory>(player).cloned()
+    }
+
+    /// The mines a player owns, with their construction sites, ordered by position
+    pub fn mines(&mut self, player_id: &str) -> Vec<(Mine, ConstructionSite, Position)> {
+        let owner = match self.player(player_id) {
+            Some(e) => e,
+            None => return Vec::new(),
+        };
+        let world = &mut self.app.world;
+        let mut mines: Vec<_> = world
+            .query::<(&Mine, &ConstructionSite, &Position, &Parent)>()
+            .iter(world)
+            .filter(|(_, _, _, parent)| parent.0 == owner)
+            .map(|(m, c, p, _)| (m.clone(), c.clone(), p.clone()))
+            .collect();
+        mines.sort_by(|a, b| by_position(&a.2, &b.2));
+        mines
+    }
+
+    /// The mobile units a player has on a satellite, ordered by id
+    pub fn mobiles(&mut self, player_id: &str) -> Vec<(UnitId, Velocity, Position)> {
+        let owner = match self.player(player_id) {
+            Some(e) => e,
+            None => return Vec::new(),
+        };
+        let world = &mut self.app.world;
+        let mut mobiles: Vec<_> = world
+            .query::<(&UnitId, &Velocity, &Position, &Parent)>()
+            .iter(world)
+            .filter(|(_, _, _, parent)| parent.0 == owner)
+            .map(|(id, v, p, _)| (*id, v.clone(), p.clone()))
+            .collect();
+        mobiles.sort_by_key(|m| m.0);
+        mobiles
+    }
+
+    /// The units a player has travelling between satellites, in no particular order
+    pub fn transits(&mut self, player_id: &str) -> Vec<Transit> {
+        let owner = match self.player(player_id) {
+            Some(e) => e,
+            None => return Vec::new(),
+        };
+        let world = &mut self.app.world;
+        world
+            .query::<(&Transit, &Parent)>()
+            .iter(world)
+            .filter(|(_, parent)| parent.0 == owner)
+            .map(|(t, _)| t.clone())
+            .collect()
+    }
+
+    /// The weapons a player owns, built or not, with where they are unless they're in transit
+    pub fn weapons(&mut self, player_id: &str) -> Vec<(Entity, Weapon, Option<Position>)> {
+        let owner = match self.player(player_id) {
+            Some(e) => e,
+            None => return Vec::new(),
+        };
+        let world = &mut self.app.world;
+        world
+            .query::<(Entity, &Weapon, Option<&Position>, &Parent)>()
+            .iter(world)
+            .filter(|(_, _, _, parent)| parent.0 == owner)
+            .map(|(e, w, p, _)| (e, w.clone(), p.cloned()))
+            .collect()
+    }
+
+    /// The structures a player owns, ordered by position
+    pub fn structures(&mut self, player_id: &str) -> Vec<(Structure, Position)> {
+        let owner = match self.player(player_id) {
+            Some(e) => e,
+            None => return Vec::new(),
+        };
+        let world = &mut self.app.world;
+        let mut structures: Vec<_> = world
+            .query::<(&Structure, &Position, &Parent)>()
+            .iter(world)
+            .filter(|(_, _, parent)| parent.0 == owner)
+            .map(|(s, p, _)| (s.clone(), p.clone()))
+            .collect();
+        structures.sort_by(|a, b| by_position(&a.1, &b.1));
+        structures
+    }
+
+    /// The untapped deposits left in the universe, ordered by position
+    pub fn deposits(&mut self) -> Vec<(Deposit, Position)> {
+        let world = &mut self.app.world;
+        let mut deposits: Vec<_> = world
+            .query::<(&Deposit, &Position)>()
+            .iter(world)
+            .map(|(d, p)| (d.clone(), p.clone()))
+            .collect();
+        deposits.sort_by(|a, b| by_position(&a.1, &b.1));
+        deposits
+    }
+}
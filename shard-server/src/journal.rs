@@ -0,0 +1,437 @@
+//! Persistence of the shard's history as a journal of events, and rebuilding the world from it
+
+use std::collections::HashMap;
+
+use bevy::app::Events;
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use wasmcolonies_domain::{ColonyEvent, EventStore, MemoryEventStore};
+use wasmcolonies_protocol::UnitType;
+
+use crate::combat::{begin_weapon, Weapon};
+use crate::command::begin_mine;
+use crate::construction::ConstructionSite;
+use crate::core::{Position, UnitId, UnitIds, Velocity};
+use crate::lifecycle::{Match, MatchPhase, MatchResult};
+use crate::movement::movement;
+use crate::player::{spawn_player, Player, Score};
+use crate::procgen::Deposit;
+use crate::resources::{Inventory, Mine};
+use crate::rules::GameParameters;
+use crate::simulation;
+use crate::snapshot::{self, Snapshot};
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::travel::launch;
+
+/// Where the shard's events are kept
+pub struct Journal(pub Box<dyn EventStore>);
+
+impl Default for Journal {
+    /// A journal kept in memory, lost when the shard stops
+    fn default() -> Journal {
+        Journal(Box::new(MemoryEventStore::default()))
+    }
+}
+
+/// What a previous run of the shard left behind, to be restored before the first tick: the
+/// most recent usable snapshot, if any, and the events journaled after it
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub snapshot: Option<Snapshot>,
+    pub events: Vec<ColonyEvent>,
+}
+
+/// Appends every event raised during the tick to the journal
+pub fn journal(mut journal: ResMut<Journal>, mut events: EventReader<ColonyEvent>) {
+    let events: Vec<ColonyEvent> = events.iter().cloned().collect();
+    if events.is_empty() {
+        return;
+    }
+    if let Err(e) = journal.0.append(&events) {
+        error!("Failed to journal {} events: {}", events.len(), e);
+    }
+}
+
+/// Rebuilds the world by applying the events of a previous run, in order, on top of either
+/// the snapshot they follow or the freshly generated universe. Each tick finished while the
+/// match was running moves the mobile units and runs the simulation once, just as it did when
+/// the tick was first played, and the game clock resumes after the last finished tick
+pub fn replay(world: &mut World) {
+    let Replay { snapshot, events } = match world.remove_resource::<Replay>() {
+        Some(replay) => replay,
+        None => return,
+    };
+    let mut players: HashMap<String, Entity> = match snapshot {
+        Some(snapshot) => {
+            info!("Restoring snapshot of tick {}", snapshot.tick);
+            snapshot::restore(world, snapshot)
+        }
+        None => HashMap::new(),
+    };
+    if events.is_empty() {
+        return;
+    }
+    info!("Replaying {} journaled events", events.len());
+
+    let params = world
+        .get_resource::<GameParameters>()
+        .cloned()
+        .unwrap_or_default();
+    let starting_inventory = Inventory::with_ore(&params.starting_inventory);
+    let mut motion = SystemStage::single_threaded().with_system(movement.system());
+    let mut simulation = SystemStage::single_threaded().with_system_set(simulation());
+    let mut queue = CommandQueue::default();
+    for event in events {
+        match event {
+            ColonyEvent::PlayerJoined {
+                player_id,
+                actor_key,
+                location,
+                base_id,
+                ..
+            } => {
+                let base_id = journaled_id(world, base_id);
+                let mut commands = Commands::new(&mut queue, world);
+                let player = Player {
+                    id: player_id.clone(),
+                    actor_key,
+                };
+                let entity = spawn_player(
+                    &mut commands,
+                    player,
+                    base_id,
+                    Position::from(&location),
+                    starting_inventory.clone(),
+                );
+                players.insert(player_id, entity);
+            }
+            ColonyEvent::PlayerLeft { player_id, .. } => {
+                if let Some(entity) = players.remove(&player_id) {
+                    Commands::new(&mut queue, world)
+                        .entity(entity)
+                        .despawn_recursive();
+                }
+            }
+            ColonyEvent::UnitConstructionBegan {
+                player_id,
+                utype: UnitType::Mine(_),
+                location,
+                yield_in,
+                cost,
+                unit_id,
+                ..
+            } => {
+                let position = Position::from(&location);
+                let deposit = world
+                    .query::<(Entity, &Deposit, &Position)>()
+                    .iter(world)
+                    .find(|(_, _, p)| **p == position)
+                    .map(|(e, d, _)| (e, d.clone()));
+                match (players.get(&player_id), deposit) {
+                    (Some(owner), Some((deposit_entity, deposit))) => {
+                        if let Some(mut inventory) = world.get_mut::<Inventory>(*owner) {
+                            inventory.spend(&cost);
+                        }
+                        let id = journaled_id(world, unit_id);
+                        let mut commands = Commands::new(&mut queue, world);
+                        begin_mine(
+                            &mut commands,
+                            *owner,
+                            id,
+                            deposit_entity,
+                            &deposit,
+                            &position,
+                            yield_in,
+                            params.ore(&deposit.ore).map_or(0, |o| o.yield_rate),
+                        );
+                    }
+                    _ => warn!(
+                        "Journaled mine for player {} at {:?} can't be rebuilt",
+                        player_id, location
+                    ),
+                }
+            }
+            ColonyEvent::UnitConstructionBegan {
+                player_id,
+                utype,
+                location,
+                yield_in,
+                cost,
+                unit_id,
+                ..
+            } if utype.kind().is_some() => match (players.get(&player_id), utype.kind(), unit_id) {
+                (Some(owner), Some(kind), Some(id)) => {
+                    if let Some(mut inventory) = world.get_mut::<Inventory>(*owner) {
+                        inventory.spend(&cost);
+                    }
+                    let id = journaled_id(world, Some(id));
+                    let mut commands = Commands::new(&mut queue, world);
+                    begin_weapon(
+                        &mut commands,
+                        *owner,
+                        id,
+                        kind,
+                        &Position::from(&location),
+                        yield_in,
+                    );
+                }
+                _ => warn!(
+                    "Journaled {:?} for player {} can't be rebuilt",
+                    utype, player_id
+                ),
+            },
+            ColonyEvent::OreCollected {
+                player_id,
+                location,
+                ore,
+                qty,
+                ..
+            } => {
+                let position = Position::from(&location);
+                let owner = players.get(&player_id).copied();
+                let collected = world
+                    .query::<(&mut Mine, &Position, &Parent)>()
+                    .iter_mut(world)
+                    .find(|(m, p, parent)| {
+                        **p == position && Some(parent.0) == owner && *m.ore() == ore
+                    })
+                    .map(|(mut mine, _, _)| mine.withdraw(qty))
+                    .is_some();
+                match owner {
+                    Some(owner) if collected => {
+                        if let Some(mut inventory) = world.get_mut::<Inventory>(owner) {
+                            inventory.add(ore, qty);
+                        }
+                    }
+                    _ => warn!(
+                        "Journaled collection by player {} at {:?} can't be repeated",
+                        player_id, location
+                    ),
+                }
+            }
+            ColonyEvent::UnitSteered {
+                player_id,
+                unit_id,
+                velocity,
+                ..
+            } => {
+                let owner = players.get(&player_id).copied();
+                match world
+                    .query::<(&mut Velocity, &UnitId, &Parent)>()
+                    .iter_mut(world)
+                    .find(|(_, id, parent)| id.0 == unit_id && Some(parent.0) == owner)
+                {
+                    Some((mut steered, _, _)) => *steered = Velocity::from(velocity),
+                    None => warn!(
+                        "Journaled steering of player {}'s unit {} can't be repeated",
+                        player_id, unit_id
+                    ),
+                }
+            }
+            ColonyEvent::UnitLaunched {
+                player_id,
+                unit_id,
+                location,
+                destination,
+                arrive_in,
+                ..
+            } => {
+                let owner = players.get(&player_id).copied();
+                let unit = world
+                    .query_filtered::<(Entity, &UnitId, &Parent), (With<Velocity>, With<Position>)>(
+                    )
+                    .iter(world)
+                    .find(|(_, id, parent)| id.0 == unit_id && Some(parent.0) == owner)
+                    .map(|(e, _, _)| e);
+                match unit {
+                    Some(unit) => launch(
+                        &mut Commands::new(&mut queue, world),
+                        unit,
+                        &Position::from(&location),
+                        Position::from(&destination),
+                        arrive_in,
+                    ),
+                    None => warn!(
+                        "Journaled launch of player {}'s unit {} can't be repeated",
+                        player_id, unit_id
+                    ),
+                }
+            }
+            ColonyEvent::StructureAttacked {
+                player_id,
+                unit_id,
+                target,
+                damage,
+                ..
+            } => {
+                let owner = players.get(&player_id).copied();
+                let fired = world
+                    .query_filtered::<(&mut Weapon, &UnitId, &Parent), Without<ConstructionSite>>()
+                    .iter_mut(world)
+                    .find(|(w, id, parent)| {
+                        id.0 == unit_id && Some(parent.0) == owner && w.is_loaded()
+                    })
+                    .map(|(mut weapon, _, _)| {
+                        let reload = params.weapon(weapon.kind()).map_or(0, |w| w.reload_ticks);
+                        weapon.fire(reload);
+                    });
+                let target = Position::from(&target);
+                let hit = world
+                    .query::<(&mut Structure, &Position)>()
+                    .iter_mut(world)
+                    .find(|(_, p)| **p == target)
+                    .map(|(mut structure, _)| structure.damage(damage));
+                if fired.is_none() || hit.is_none() {
+                    warn!(
+                        "Journaled attack by player {}'s weapon {} can't be repeated",
+                        player_id, unit_id
+                    );
+                }
+            }
+            ColonyEvent::StructureDestroyed {
+                player_id,
+                location,
+                ..
+            } => {
+                let position = Position::from(&location);
+                let owner = players.get(&player_id).copied();
+                let destroyed = world
+                    .query::<(Entity, &Structure, &Position, &Parent)>()
+                    .iter(world)
+                    .find(|(_, _, p, parent)| **p == position && Some(parent.0) == owner)
+                    .map(|(e, _, _, _)| e);
+                match destroyed {
+                    Some(entity) => Commands::new(&mut queue, world)
+                        .entity(entity)
+                        .despawn_recursive(),
+                    None => warn!(
+                        "Journaled destruction of player {}'s structure at {:?} can't be repeated",
+                        player_id, location
+                    ),
+                }
+            }
+            ColonyEvent::RepairBegan {
+                player_id,
+                location,
+                hp,
+                cost,
+                ..
+            } => {
+                let position = Position::from(&location);
+                let owner = players.get(&player_id).copied();
+                let repaired = world
+                    .query::<(&mut Structure, &Position, &Parent)>()
+                    .iter_mut(world)
+                    .find(|(_, p, parent)| **p == position && Some(parent.0) == owner)
+                    .map(|(mut structure, _, _)| structure.repair(hp));
+                match (owner, repaired) {
+                    (Some(owner), Some(())) => {
+                        if let Some(mut inventory) = world.get_mut::<Inventory>(owner) {
+                            inventory.spend(&cost);
+                        }
+                    }
+                    _ => warn!(
+                        "Journaled repair by player {} at {:?} can't be repeated",
+                        player_id, location
+                    ),
+                }
+            }
+            ColonyEvent::StructureUpgraded {
+                player_id,
+                location,
+                level,
+                cost,
+                ..
+            } => {
+                let position = Position::from(&location);
+                let owner = players.get(&player_id).copied();
+                let upgraded = world
+                    .query::<(&mut Structure, &Position, &Parent)>()
+                    .iter_mut(world)
+                    .find(|(_, p, parent)| **p == position && Some(parent.0) == owner)
+                    .and_then(|(mut structure, _, _)| {
+                        let upgrade = params.base_upgrade(structure.level())?;
+                        structure.upgrade(upgrade.max_hp, upgrade.ar);
+                        Some(structure.level())
+                    });
+                match (owner, upgraded) {
+                    (Some(owner), Some(reached)) if reached == level => {
+                        if let Some(mut inventory) = world.get_mut::<Inventory>(owner) {
+                            inventory.spend(&cost);
+                        }
+                    }
+                    _ => warn!(
+                        "Journaled upgrade by player {} at {:?} can't be repeated",
+                        player_id, location
+                    ),
+                }
+            }
+            ColonyEvent::ScoresPublished { scores, .. } => {
+                for published in scores {
+                    if let Some(mut score) = players
+                        .get(&published.player_id)
+                        .and_then(|entity| world.get_mut::<Score>(*entity))
+                    {
+                        score.value = published.score;
+                    }
+                }
+            }
+            ColonyEvent::MatchStarted { .. } => {
+                if let Some(mut state) = world.get_resource_mut::<Match>() {
+                    state.phase = MatchPhase::Running;
+                }
+            }
+            ColonyEvent::MatchEnded {
+                tick,
+                winners,
+                scores,
+            } => {
+                world.insert_resource(Match {
+                    phase: MatchPhase::Ended,
+                    result: Some(MatchResult {
+                        tick,
+                        winners,
+                        scores,
+                    }),
+                });
+            }
+            ColonyEvent::TickFinished(tick) => {
+                // Only ticks played while the match was running moved the world on
+                let running =
+                    world.get_resource::<Match>().map(|m| m.phase) == Some(MatchPhase::Running);
+                if running {
+                    motion.run(world);
+                    simulation.run(world);
+                    // The simulation announces what it does, e.g. completed construction, but
+                    // those events are already in the journal
+                    if let Some(mut raised) = world.get_resource_mut::<Events<ColonyEvent>>() {
+                        raised.clear();
+                    }
+                }
+                world.insert_resource(GameTick(tick + 1));
+            }
+            // Completed construction is worked out again by the simulation
+            ColonyEvent::UnitConstructed { .. } => {}
+            ColonyEvent::UnitConstructionBegan { .. } | ColonyEvent::None => {}
+        }
+        queue.apply(world);
+    }
+    info!(
+        "Resuming at tick {}",
+        world.get_resource::<GameTick>().map(|t| t.0).unwrap_or(0)
+    );
+}
+
+/// The id a journaled unit was given, reserved so it's never issued again. Units journaled
+/// before they were given ids get a new one as they're rebuilt
+fn journaled_id(world: &mut World, id: Option<u64>) -> UnitId {
+    let mut ids = world.get_resource_or_insert_with(UnitIds::default);
+    match id {
+        Some(id) => {
+            ids.reserve(UnitId(id));
+            UnitId(id)
+        }
+        None => ids.issue(),
+    }
+}
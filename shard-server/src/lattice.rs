@@ -9,94 +9,84 @@
 // able to push them on the wire. The "real" Invocation and InvocationResponse types are in the wasmcloud-host
 // crate in the dispatch module because we need to implement other traits on those types.
 
-use crate::Result;
+use crate::transport::{ColonyFault, ColonyTransport};
 use data_encoding::HEXUPPER;
 use ring::digest::{Context, Digest, SHA256};
 use serde::{Deserialize, Serialize};
 use std::{io::Read, time::Duration};
-use tracing::error;
 use uuid::Uuid;
 use wascap::jwt::Claims;
 use wascap::prelude::KeyPair;
 use wasmcolonies_protocol::{
-    deserialize, serialize, ColonyCommand, GameStateColonyView, PlayerTick, PlayerTickResponse,
-    OP_PLAYER_TICK,
+    deserialize, serialize, PlayerTick, PlayerTickResponse, OP_PLAYER_TICK,
 };
 
 const URL_SCHEME: &str = "wasmbus";
-const RPC_TIMEOUT_MILLIS: u64 = 1_000;
 
-pub struct ColonyInvoker {
+/// Delivers ticks to colony actors running in wasmcloud hosts on a NATS lattice, using
+/// the wasmbus RPC protocol. Fuel and memory limits are up to the hosts running the actors;
+/// the shard can only enforce the deadline
+pub struct LatticeTransport {
     nc: nats::Connection,
     hk: KeyPair,
+    prefix: Option<String>,
+    deadline: Duration,
 }
 
-impl ColonyInvoker {
-    pub fn new(nc: nats::Connection) -> ColonyInvoker {
-        ColonyInvoker {
+impl LatticeTransport {
+    pub fn new(
+        nc: nats::Connection,
+        prefix: Option<String>,
+        deadline: Duration,
+    ) -> LatticeTransport {
+        LatticeTransport {
             nc,
             hk: KeyPair::new_server(),
+            prefix,
+            deadline,
         }
     }
+}
 
-    pub fn fetch_commands(
+impl ColonyTransport for LatticeTransport {
+    fn player_tick(
         &self,
-        player_id: &str,
         actor_key: &str,
-        gs: GameStateColonyView,
-    ) -> Result<Vec<ColonyCommand>> {
-        let pt = PlayerTick {
-            tick: 0,
-            player_id: player_id.to_string(),
-            game_state: Some(gs),
-        };
-
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault> {
         let inv = Invocation::new(
             &self.hk,
             Entity::Actor("system".to_string()),
             Entity::Actor(actor_key.to_string()),
             OP_PLAYER_TICK,
-            serialize(pt).unwrap(),
-        );
-        let subject = &rpc_subject(None, &actor_key);
-        let res = self.nc.request_timeout(
-            subject,
-            &serialize(inv).unwrap(),
-            Duration::from_millis(RPC_TIMEOUT_MILLIS),
+            serialize(tick).map_err(unavailable)?,
         );
-        let tr: std::result::Result<PlayerTickResponse, std::io::Error> = res
-            .and_then(|m| {
-                deserialize::<InvocationResponse>(&m.data)
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e)))
-            })
-            .and_then(|ir| {
-                if let Some(e) = ir.error {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("{}", e),
-                    ))
-                } else {
-                    deserialize::<PlayerTickResponse>(&ir.msg).map_err(|e| {
-                        std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e))
-                    })
-                }
-            });
-        Ok(match tr {
-            Ok(tr) => tr.commands,
-            Err(e) => {
-                error!("{}", e);
-                vec![ColonyCommand::Pass(0)]
-            }
-        })
+        let subject = &rpc_subject(self.prefix.as_deref(), actor_key);
+        let msg = self
+            .nc
+            .request_timeout(
+                subject,
+                &serialize(inv).map_err(unavailable)?,
+                self.deadline,
+            )
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::TimedOut => ColonyFault::Timeout,
+                _ => unavailable(e),
+            })?;
+        let ir = deserialize::<InvocationResponse>(&msg.data).map_err(unavailable)?;
+        match ir.error {
+            Some(e) => Err(ColonyFault::Trap(e)),
+            None => deserialize::<PlayerTickResponse>(&ir.msg).map_err(unavailable),
+        }
     }
 }
 
-fn rpc_subject(prefix: Option<String>, actor: &str) -> String {
-    format!(
-        "wasmbus.rpc.{}.{}",
-        prefix.as_ref().unwrap_or(&"default".to_string()),
-        actor
-    )
+fn unavailable(e: impl std::fmt::Display) -> ColonyFault {
+    ColonyFault::Unavailable(format!("{}", e))
+}
+
+fn rpc_subject(prefix: Option<&str>, actor: &str) -> String {
+    format!("wasmbus.rpc.{}.{}", prefix.unwrap_or("default"), actor)
 }
 
 /// An immutable representation of an invocation within wasmcloud
@@ -0,0 +1,190 @@
+//! The wasmColonies shard: the components and systems of the game world, and the schedule that
+//! plays it one tick at a time. The `wcshard` binary runs a shard from its configuration; the
+//! [`harness`](harness) module runs one headless, a tick at a time, for tests
+
+use bevy::{core::FixedTimestep, prelude::*};
+use std::error::Error;
+use wasmcolonies_domain::ColonyEvent;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+pub mod admission;
+pub mod cli;
+pub mod combat;
+pub mod command;
+pub mod construction;
+pub mod core;
+pub mod harness;
+pub mod journal;
+pub mod lattice;
+pub mod lifecycle;
+pub mod lobby;
+pub mod movement;
+pub mod player;
+pub mod procgen;
+pub mod recording;
+pub mod resources;
+pub mod roster;
+pub mod rules;
+pub mod scoring;
+pub mod snapshot;
+pub mod structure;
+pub mod tick;
+pub mod transport;
+pub mod travel;
+pub mod view;
+pub mod wasmhost;
+
+use crate::core::UnitIds;
+use combat::{arm, reload, CombatReports};
+use command::{apply_colony_commands, ColonyCommands};
+use construction::construction;
+use journal::{journal, replay, Journal, Replay};
+use lifecycle::{kick_off, match_running, referee, Match};
+use lobby::{membership, Lobby};
+use movement::movement;
+use player::colony_commands;
+use procgen::big_bang;
+use recording::record_tick;
+use resources::mines;
+use rules::{load_construction_rules, load_repair_rate, ColoniesStage, WasmColoniesLabels};
+use scoring::{publish_scores, scoring};
+use snapshot::take_snapshot;
+use structure::repair;
+use tick::{end_of_tick, GameTick};
+use travel::transit;
+
+/// The shard's game loop: the big bang (or the restoration of a previous run) and the stages
+/// that play each tick. The stages are nested in the [`Tick`](ColoniesStage::Tick) stage, which
+/// plays a tick every `step` seconds, or once per update of the app without one. Systems are
+/// added to a tick's stages through [`AppBuilder::stage`].
+///
+/// The app must have bevy's core plugin, the [`GameParameters`](rules::GameParameters) and a
+/// [`ColonyInvoker`](transport::ColonyInvoker). A [`Lobby`](Lobby), [`Journal`](Journal) and
+/// [`Replay`](Replay) can be inserted as well; the shard starts with no players, no history and
+/// an in-memory journal otherwise
+#[derive(Debug, Clone, Default)]
+pub struct ShardPlugin {
+    pub step: Option<f64>,
+}
+
+impl ShardPlugin {
+    /// Plays a tick every `step` seconds
+    pub fn with_step(step: f64) -> ShardPlugin {
+        ShardPlugin { step: Some(step) }
+    }
+}
+
+impl Plugin for ShardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Lobby>()
+            .init_resource::<Journal>()
+            .init_resource::<Replay>()
+            .init_resource::<GameTick>()
+            .init_resource::<CombatReports>()
+            .init_resource::<Match>()
+            .init_resource::<UnitIds>()
+            .add_event::<ColonyCommands>()
+            .add_event::<ColonyEvent>()
+            .add_startup_system(big_bang.system().label(WasmColoniesLabels::BigBang))
+            .add_startup_system(load_construction_rules.system())
+            .add_startup_system(load_repair_rate.system())
+            .add_startup_system_to_stage(StartupStage::PostStartup, replay.exclusive_system())
+            .add_stage_before(CoreStage::Update, ColoniesStage::Tick, tick(self.step));
+    }
+}
+
+/// Every stage of a tick, in order, in a schedule of their own. A tick runs the whole schedule,
+/// so no stage can be skipped or repeated when the timestep falls behind or catches up
+fn tick(step: Option<f64>) -> Schedule {
+    let mut tick = Schedule::default();
+    if let Some(step) = step {
+        tick.set_run_criteria(FixedTimestep::step(step));
+    }
+    tick.add_stage(
+        ColoniesStage::Membership,
+        SystemStage::single_threaded()
+            .with_system(membership.system().label(WasmColoniesLabels::Membership))
+            .with_system(kick_off.system().after(WasmColoniesLabels::Membership)),
+    )
+    .add_stage_after(
+        ColoniesStage::Membership,
+        ColoniesStage::ActorRpc,
+        SystemStage::parallel().with_system_set(
+            SystemSet::new()
+                .with_run_criteria(match_running.system())
+                .with_system(colony_commands.system()),
+        ),
+    )
+    .add_stage_after(
+        ColoniesStage::ActorRpc,
+        ColoniesStage::Commands,
+        SystemStage::parallel().with_system_set(
+            SystemSet::new()
+                .with_run_criteria(match_running.system())
+                .with_system(apply_colony_commands.system()),
+        ),
+    )
+    .add_stage_after(
+        ColoniesStage::Commands,
+        ColoniesStage::Movement,
+        SystemStage::parallel().with_system_set(
+            SystemSet::new()
+                .with_run_criteria(match_running.system())
+                .with_system(movement.system()),
+        ),
+    )
+    .add_stage_after(
+        ColoniesStage::Movement,
+        ColoniesStage::Resources,
+        SystemStage::parallel()
+            .with_system_set(simulation().with_run_criteria(match_running.system())),
+    )
+    .add_stage_after(
+        ColoniesStage::Resources,
+        ColoniesStage::Scoring,
+        SystemStage::parallel().with_system_set(
+            SystemSet::new()
+                .with_run_criteria(match_running.system())
+                .with_system(scoring.system().label(WasmColoniesLabels::Scoring))
+                .with_system(
+                    publish_scores
+                        .system()
+                        .label(WasmColoniesLabels::Scoreboard)
+                        .after(WasmColoniesLabels::Scoring),
+                )
+                .with_system(referee.system().after(WasmColoniesLabels::Scoreboard)),
+        ),
+    )
+    .add_stage_after(
+        ColoniesStage::Scoring,
+        ColoniesStage::EndOfTick,
+        SystemStage::single_threaded().with_system(end_of_tick.system()),
+    )
+    .add_stage_after(
+        ColoniesStage::EndOfTick,
+        ColoniesStage::Journal,
+        SystemStage::single_threaded()
+            .with_system(journal.system().label(WasmColoniesLabels::Journal))
+            .with_system(take_snapshot.system().after(WasmColoniesLabels::Journal))
+            .with_system(record_tick.system().after(WasmColoniesLabels::Journal)),
+    );
+    tick
+}
+
+/// The systems that advance the world by one tick, independent of what colonies do. These
+/// run once per tick in the resources stage, and once for every tick replayed from the journal.
+/// Mines start yielding the tick they're completed, so mining comes after construction
+pub fn simulation() -> SystemSet {
+    SystemSet::new()
+        .with_system(mines.system().after(WasmColoniesLabels::Construction))
+        .with_system(
+            construction
+                .system()
+                .label(WasmColoniesLabels::Construction),
+        )
+        .with_system(transit.system())
+        .with_system(reload.system())
+        .with_system(repair.system())
+        .with_system(arm.system().after(WasmColoniesLabels::Construction))
+}
@@ -0,0 +1,182 @@
+//! The match: waiting for players, playing until someone wins, and stopping the shard once
+//! it's over
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use bevy::{app::AppExit, ecs::schedule::ShouldRun, prelude::*};
+use serde::{Deserialize, Serialize};
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::PlayerScore;
+
+use crate::player::{Player, Score};
+use crate::rules::{GameParameters, WinCondition};
+use crate::scoring::scoreboard;
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::Result;
+
+/// Where the match is in its lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    /// Waiting for enough players to join. Ticks pass, but colonies aren't invoked
+    Lobby,
+    Running,
+    /// The match has been decided, and no more of it is played
+    Ended,
+}
+
+impl Default for MatchPhase {
+    fn default() -> MatchPhase {
+        MatchPhase::Lobby
+    }
+}
+
+/// How a match turned out
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchResult {
+    /// The tick during which the match was decided
+    pub tick: u64,
+    /// The players who won. More than one player wins if they tie, and no one does if every
+    /// base was destroyed
+    pub winners: Vec<String>,
+    /// The final scores, highest first
+    pub scores: Vec<PlayerScore>,
+}
+
+/// The match played on the shard
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Match {
+    pub phase: MatchPhase,
+    pub result: Option<MatchResult>,
+}
+
+/// Where the results of the match are written once it ends
+#[derive(Clone, Debug)]
+pub struct ResultsFile(pub PathBuf);
+
+/// Run criteria for everything that plays the match, which only happens while it's running
+pub fn match_running(state: Res<Match>) -> ShouldRun {
+    if state.phase == MatchPhase::Running {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Starts the match once enough players have joined, and stops the shard if the match is over
+pub fn kick_off(
+    params: Res<GameParameters>,
+    tick: Res<GameTick>,
+    mut state: ResMut<Match>,
+    players: Query<&Player>,
+    mut events: EventWriter<ColonyEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    match state.phase {
+        MatchPhase::Lobby => {
+            let mut ids: Vec<String> = players.iter().map(|p| p.id.clone()).collect();
+            if ids.len() < params.match_rules.min_players {
+                return;
+            }
+            ids.sort();
+            info!("Match started with {} players", ids.len());
+            state.phase = MatchPhase::Running;
+            events.send(ColonyEvent::MatchStarted {
+                tick: tick.0,
+                players: ids,
+            });
+        }
+        MatchPhase::Running => {}
+        MatchPhase::Ended => exit.send(AppExit),
+    }
+}
+
+/// Ends the match once its win condition has been met, recording the results and stopping
+/// the shard
+#[allow(clippy::too_many_arguments)]
+pub fn referee(
+    params: Res<GameParameters>,
+    tick: Res<GameTick>,
+    mut state: ResMut<Match>,
+    players: Query<(Entity, &Player, &Score)>,
+    bases: Query<&Parent, With<Structure>>,
+    results_file: Option<Res<ResultsFile>>,
+    mut events: EventWriter<ColonyEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let scores = scoreboard(players.iter().map(|(_, p, s)| (p, s)));
+    let mut winners = match params.match_rules.win_condition {
+        None => return,
+        Some(WinCondition::LastBaseStanding) => {
+            let standing: Vec<String> = players
+                .iter()
+                .filter(|(entity, _, _)| bases.iter().any(|parent| parent.0 == *entity))
+                .map(|(_, p, _)| p.id.clone())
+                .collect();
+            if standing.len() > 1 {
+                return;
+            }
+            standing
+        }
+        Some(WinCondition::FirstToScore(target)) => {
+            let top = scores.first().map(|s| s.score).unwrap_or(0);
+            if top < target {
+                return;
+            }
+            leaders(&scores)
+        }
+        Some(WinCondition::HighestScoreAt(end)) => {
+            if tick.0 < end {
+                return;
+            }
+            leaders(&scores)
+        }
+    };
+    winners.sort();
+
+    info!("Match over at tick {}, won by {:?}", tick.0, winners);
+    events.send(ColonyEvent::MatchEnded {
+        tick: tick.0,
+        winners: winners.clone(),
+        scores: scores.clone(),
+    });
+    let result = MatchResult {
+        tick: tick.0,
+        winners,
+        scores,
+    };
+    if let Some(file) = results_file {
+        match write_results(&file.0, &result) {
+            Ok(_) => info!("Wrote match results to {}", file.0.display()),
+            Err(e) => error!(
+                "Failed to write match results to {}: {}",
+                file.0.display(),
+                e
+            ),
+        }
+    }
+    state.phase = MatchPhase::Ended;
+    state.result = Some(result);
+    exit.send(AppExit);
+}
+
+/// The players sharing the highest score
+fn leaders(scores: &[PlayerScore]) -> Vec<String> {
+    let top = match scores.first() {
+        Some(s) => s.score,
+        None => return Vec::new(),
+    };
+    scores
+        .iter()
+        .take_while(|s| s.score == top)
+        .map(|s| s.player_id.clone())
+        .collect()
+}
+
+fn write_results(path: &Path, result: &MatchResult) -> Result<()> {
+    serde_json::to_writer_pretty(File::create(path)?, result)?;
+    Ok(())
+}
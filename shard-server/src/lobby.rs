@@ -0,0 +1,309 @@
+//! Players joining and leaving a running shard
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+use std::thread;
+
+use bevy::prelude::*;
+use wascap::prelude::KeyPair;
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{
+    deserialize, serialize, MembershipRequest, MembershipResponse, SignedMembershipRequest,
+};
+
+use crate::admission::{self, AdmissionPolicy};
+use crate::core::{Position, UnitIds};
+use crate::player::{spawn_player, Player, BASE_SPACING};
+use crate::procgen::Satellite;
+use crate::resources::Inventory;
+use crate::rules::GameParameters;
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::transport::ColonyInvoker;
+use crate::Result;
+
+/// A change to the shard's players, applied at the start of the next tick
+#[derive(Debug, Clone)]
+pub enum Membership {
+    Join {
+        player: Player,
+        start: Option<Position>,
+    },
+    Leave {
+        player_id: String,
+        /// The colony that asked for the player to leave, when the request came from outside
+        /// the shard. The player only leaves if they joined with this colony
+        actor_key: Option<String>,
+    },
+}
+
+/// Collects membership changes from wherever they arrive (the roster, the lattice, or code
+/// in the same process) for the [`membership`](membership) system to apply
+pub struct Lobby {
+    requests: Mutex<Receiver<Membership>>,
+    sender: Mutex<Sender<Membership>>,
+}
+
+impl Default for Lobby {
+    fn default() -> Lobby {
+        let (tx, rx) = channel();
+        Lobby {
+            requests: Mutex::new(rx),
+            sender: Mutex::new(tx),
+        }
+    }
+}
+
+impl Lobby {
+    /// A sender for queueing membership changes from within the shard process. Players joining
+    /// this way are trusted, so their colonies must already be reachable over the shard's transport
+    pub fn sender(&self) -> Sender<Membership> {
+        self.sender.lock().unwrap().clone()
+    }
+
+    /// Starts accepting [`SignedMembershipRequest`](SignedMembershipRequest)s on the lattice,
+    /// letting them in through a [`Gatekeeper`](Gatekeeper) with the given admission policy
+    pub fn listen_on_lattice(
+        &self,
+        nc: &nats::Connection,
+        prefix: Option<&str>,
+        policy: AdmissionPolicy,
+    ) -> Result<()> {
+        let subject = membership_subject(prefix);
+        let sub = nc.subscribe(&subject)?;
+        let sender = self.sender();
+        let mut gatekeeper = Gatekeeper::new(policy);
+        info!("Accepting players on {}", subject);
+        thread::spawn(move || {
+            for msg in sub.iter() {
+                let response = match deserialize::<SignedMembershipRequest>(&msg.data) {
+                    Ok(signed) => gatekeeper
+                        .admit(&signed)
+                        .and_then(|m| sender.send(m).map_err(|_| "shard is stopping".to_string())),
+                    Err(e) => Err(format!("{}", e)),
+                };
+                let response = match response {
+                    Ok(_) => MembershipResponse {
+                        accepted: true,
+                        reason: None,
+                    },
+                    Err(reason) => MembershipResponse {
+                        accepted: false,
+                        reason: Some(reason),
+                    },
+                };
+                match serialize(response) {
+                    Ok(bytes) => {
+                        if let Err(e) = msg.respond(bytes) {
+                            warn!("Failed to answer membership request: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to serialize membership response: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Checks membership requests arriving from outside the shard process. A request must be
+/// signed with the module key of the colony it's for, so only whoever holds a colony's key can
+/// bring players into the game with it or take them out, and each request can only be used once
+pub struct Gatekeeper {
+    policy: AdmissionPolicy,
+    nonces: HashSet<String>,
+}
+
+impl Gatekeeper {
+    pub fn new(policy: AdmissionPolicy) -> Gatekeeper {
+        Gatekeeper {
+            policy,
+            nonces: HashSet::new(),
+        }
+    }
+
+    /// Turns a signed request into a membership change, verifying a joining colony's claims
+    /// and that the request was signed by the colony's module key
+    pub fn admit(
+        &mut self,
+        signed: &SignedMembershipRequest,
+    ) -> std::result::Result<Membership, String> {
+        let request =
+            deserialize::<MembershipRequest>(&signed.request).map_err(|e| format!("{}", e))?;
+        let (change, actor_key, nonce) = match request {
+            MembershipRequest::Join {
+                player_id,
+                colony_jwt,
+                start,
+                nonce,
+            } => {
+                let claims = admission::verify_token(&self.policy, &colony_jwt)
+                    .map_err(|e| format!("{}", e))?;
+                let change = Membership::Join {
+                    player: Player {
+                        id: player_id,
+                        actor_key: claims.subject.clone(),
+                    },
+                    start: start.as_ref().map(Position::from),
+                };
+                (change, claims.subject, nonce)
+            }
+            MembershipRequest::Leave {
+                player_id,
+                actor_key,
+                nonce,
+            } => {
+                let change = Membership::Leave {
+                    player_id,
+                    actor_key: Some(actor_key.clone()),
+                };
+                (change, actor_key, nonce)
+            }
+        };
+        KeyPair::from_public_key(&actor_key)
+            .and_then(|key| key.verify(&signed.request, &signed.signature))
+            .map_err(|_| format!("request isn't signed by colony {}", actor_key))?;
+        if !self.nonces.insert(nonce) {
+            return Err("request has already been made".to_string());
+        }
+        Ok(change)
+    }
+}
+
+fn membership_subject(prefix: Option<&str>) -> String {
+    format!(
+        "wasmcolonies.shard.{}.membership",
+        prefix.unwrap_or("default")
+    )
+}
+
+/// Applies queued membership changes. Joining players are spawned with a base at a free
+/// location; leaving players are removed along with everything they own
+#[allow(clippy::too_many_arguments)]
+pub fn membership(
+    mut commands: Commands,
+    lobby: Res<Lobby>,
+    invoker: Res<ColonyInvoker>,
+    players: Query<(Entity, &Player)>,
+    satellites: Query<&Satellite>,
+    structures: Query<&Position, With<Structure>>,
+    tick: Res<GameTick>,
+    params: Res<GameParameters>,
+    mut unit_ids: ResMut<UnitIds>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    let changes: Vec<Membership> = lobby.requests.lock().unwrap().try_iter().collect();
+    if changes.is_empty() {
+        return;
+    }
+
+    // The actor key of every player in the game, by id, including those joining this tick
+    let mut ids: HashMap<String, String> = players
+        .iter()
+        .map(|(_, p)| (p.id.clone(), p.actor_key.clone()))
+        .collect();
+    let mut occupied: Vec<Position> = structures.iter().cloned().collect();
+    let mut satellites: Vec<&Satellite> = satellites.iter().collect();
+    satellites.sort_by_key(|s| (s.sys, s.sat));
+
+    for change in changes {
+        match change {
+            Membership::Join { player, start } => {
+                if ids.contains_key(&player.id) {
+                    warn!("Player {} is already in the game", player.id);
+                    continue;
+                }
+                let position = match start
+                    .filter(|p| is_free(&satellites, &occupied, p))
+                    .or_else(|| free_location(&satellites, &occupied))
+                {
+                    Some(p) => p,
+                    None => {
+                        warn!("No room for player {} to join", player.id);
+                        continue;
+                    }
+                };
+                info!(
+                    "Player {} joined at {}/{} ({}, {})",
+                    player.id, position.sys, position.sat, position.x, position.y
+                );
+                occupied.push(position.clone());
+                ids.insert(player.id.clone(), player.actor_key.clone());
+                let base_id = unit_ids.issue();
+                events.send(ColonyEvent::PlayerJoined {
+                    tick: tick.0,
+                    player_id: player.id.clone(),
+                    actor_key: player.actor_key.clone(),
+                    location: (&position).into(),
+                    base_id: Some(base_id.0),
+                });
+                let inventory = Inventory::with_ore(&params.starting_inventory);
+                spawn_player(&mut commands, player, base_id, position, inventory);
+            }
+            Membership::Leave {
+                player_id,
+                actor_key,
+            } => {
+                let leaving = players.iter().find(|(_, p)| p.id == player_id);
+                match (leaving, actor_key) {
+                    (Some((_, player)), Some(key)) if key != player.actor_key => warn!(
+                        "Player {} can't be removed by colony {}, they joined with another",
+                        player_id, key
+                    ),
+                    (Some((entity, player)), _) if ids.contains_key(&player_id) => {
+                        info!("Player {} left", player_id);
+                        commands.entity(entity).despawn_recursive();
+                        ids.remove(&player_id);
+                        // Players admitted with the same colony share its module
+                        if !ids.values().any(|key| *key == player.actor_key) {
+                            invoker.release(&player.actor_key);
+                        }
+                        events.send(ColonyEvent::PlayerLeft {
+                            tick: tick.0,
+                            player_id: player_id.clone(),
+                        });
+                    }
+                    _ => warn!("Player {} can't leave, they aren't in the game", player_id),
+                }
+            }
+        }
+    }
+}
+
+/// Whether a base can be placed at the given position: it must be on a satellite's surface and
+/// at least a base's spacing away from every other structure
+fn is_free(satellites: &[&Satellite], occupied: &[Position], position: &Position) -> bool {
+    let on_surface = satellites.iter().any(|s| {
+        s.sys == position.sys
+            && s.sat == position.sat
+            && (0. ..s.width).contains(&position.x)
+            && (0. ..s.height).contains(&position.y)
+    });
+    on_surface
+        && occupied.iter().all(|o| {
+            o.sys != position.sys || o.sat != position.sat || o.distance(position) >= BASE_SPACING
+        })
+}
+
+/// Finds the first free location on a grid laid over each satellite in turn
+fn free_location(satellites: &[&Satellite], occupied: &[Position]) -> Option<Position> {
+    let half = BASE_SPACING / 2.;
+    satellites.iter().find_map(|s| {
+        let columns = (s.width / BASE_SPACING) as u32;
+        let rows = (s.height / BASE_SPACING) as u32;
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                Position::new(
+                    s.sys,
+                    s.sat,
+                    half + BASE_SPACING * col as f32,
+                    half + BASE_SPACING * row as f32,
+                )
+            })
+            .find(|p| is_free(satellites, occupied, p))
+    })
+}
@@ -1,63 +1,313 @@
-use bevy::{
-    app::{AppExit, ScheduleRunnerPlugin, ScheduleRunnerSettings},
-    core::FixedTimestep,
-    ecs::component::{ComponentDescriptor, StorageType},
-    log::LogPlugin,
-    prelude::*,
+use bevy::{log::LogPlugin, prelude::*};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
-use construction::ConstructionSite;
-use lattice::ColonyInvoker;
-use procgen::big_bang;
-use rules::{ColoniesStage, GameParameters, WasmColoniesLabels};
+use structopt::StructOpt;
 
-use std::error::Error;
+use wasmcolonies_domain::{ColonyEvent, EventStore, FileJournal, MemoryEventStore};
+use wcshard::cli::{Cli, Command, ShardConfig};
+use wcshard::journal::{Journal, Replay};
+use wcshard::lattice::LatticeTransport;
+use wcshard::lifecycle::ResultsFile;
+use wcshard::lobby::{Lobby, Membership};
+use wcshard::procgen;
+use wcshard::recording::{
+    self, playback_membership, verify_tick, MatchRecorder, Playback, PlaybackOutcome,
+};
+use wcshard::roster::{Roster, RosterEntry};
+use wcshard::rules::{ColoniesStage, GameParameters, WasmColoniesLabels};
+use wcshard::snapshot::{self, SnapshotSettings};
+use wcshard::transport::{ColonyInvoker, LocalTransport};
+use wcshard::wasmhost::EmbeddedHost;
+use wcshard::{Result, ShardPlugin};
+
+fn main() {
+    let cli = Cli::from_args();
+    if let Err(e) = run_cli(cli) {
+        eprintln!("wcshard: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_cli(cli: Cli) -> Result<()> {
+    let mut config = ShardConfig::load(cli.config.as_deref())?;
+    match cli.cmd {
+        Command::Run(args) => {
+            config.apply_run_args(args);
+            run(config)
+        }
+        Command::ValidateParams(args) => {
+            config.apply_params_args(args);
+            load_params(&config)?;
+            println!("{} is valid", config.params.display());
+            Ok(())
+        }
+        Command::Restore(args) => {
+            config.apply_run_args(args.run);
+            rewind(&config, args.tick)?;
+            run(config)
+        }
+        Command::Replay(args) => playback(&args.file),
+        Command::GenUniverse(args) => {
+            config.apply_params_args(args);
+            let params = load_params(&config)?;
+            let universe = procgen::generate(&params.universe, &params.ores);
+            println!("{}", serde_json::to_string_pretty(&universe)?);
+            Ok(())
+        }
+    }
+}
+
+/// Loads the game parameters named by the config, applying any seed override and refusing
+/// parameters the shard couldn't run with
+fn load_params(config: &ShardConfig) -> Result<GameParameters> {
+    let mut params = GameParameters::load_from_file(&config.params)?;
+    if let Some(seed) = config.seed {
+        params.universe.seed = seed;
+    }
+    let problems = params.problems();
+    if !problems.is_empty() {
+        return Err(format!(
+            "{} has problems:\n  {}",
+            config.params.display(),
+            problems.join("\n  ")
+        )
+        .into());
+    }
+    Ok(params)
+}
+
+fn run(config: ShardConfig) -> Result<()> {
+    if !(config.tick_rate > 0. && config.tick_rate.is_finite()) {
+        return Err(format!(
+            "Tick rate must be a positive number, not {}",
+            config.tick_rate
+        )
+        .into());
+    }
+    let params = load_params(&config)?;
+
+    let mut roster = match &config.roster {
+        Some(path) => Roster::load_from_file(path)?,
+        None => Roster::default(),
+    };
+    for module in &config.colonies {
+        roster.players.push(RosterEntry {
+            id: format!("player{}", roster.players.len() + 1),
+            module: Some(module.clone()),
+            ..Default::default()
+        });
+    }
 
-type Result<T> = std::result::Result<T, Box<dyn Error>>;
+    // Colonies are run by an embedded host, unless the shard is on a lattice, in which case
+    // they're expected to be running in a wasmcloud host there
+    let admissions = roster.admit(&config.admission, config.lattice);
+    // Logging isn't set up until the app is built
+    for (id, reason) in &admissions.rejected {
+        eprintln!("Rejected player {}: {}", id, reason);
+    }
+    let admitted = admissions.admitted;
+    let lobby = Lobby::default();
+    let cinvoker = if config.lattice {
+        let nc = nats::connect(&config.nats_url)
+            .map_err(|e| format!("Couldn't connect to NATS at {}: {}", config.nats_url, e))?;
+        lobby.listen_on_lattice(
+            &nc,
+            config.lattice_prefix.as_deref(),
+            config.admission.clone(),
+        )?;
+        ColonyInvoker::new(LatticeTransport::new(
+            nc,
+            config.lattice_prefix.clone(),
+            params.colony_limits.deadline(),
+        ))
+    } else {
+        if admitted.is_empty() {
+            return Err("No colonies were admitted to the shard".into());
+        }
+        let host = EmbeddedHost::new(params.colony_limits.clone())?;
+        let transport = LocalTransport::new(params.colony_limits.deadline());
+        for (player, _, module) in &admitted {
+            if let Some(module) = module {
+                host.start_colony(&transport, &player.actor_key, module)?;
+            }
+        }
+        ColonyInvoker::new(transport)
+    };
+    let joins = lobby.sender();
+    for (player, start, _) in admitted {
+        joins.send(Membership::Join { player, start })?;
+    }
 
-mod construction;
-mod core;
-mod lattice;
-mod player;
-mod procgen;
-mod resources;
-mod rules;
-mod structure;
+    // With a journal, the shard picks up where the previous run left off, starting from the
+    // latest snapshot the journal reaches
+    let (store, events, resume_limit): (Box<dyn EventStore>, _, _) = match &config.journal {
+        Some(path) => {
+            let store = FileJournal::open(path)
+                .map_err(|e| format!("Couldn't open journal {}: {}", path.display(), e))?;
+            let events = store
+                .events()
+                .map_err(|e| format!("Couldn't read journal {}: {}", path.display(), e))?;
+            let finished = events.iter().rev().find_map(|e| match e {
+                ColonyEvent::TickFinished(t) => Some(t + 1),
+                _ => None,
+            });
+            (Box::new(store), events, finished.unwrap_or(0))
+        }
+        None => (Box::new(MemoryEventStore::default()), Vec::new(), u64::MAX),
+    };
+    let snapshot = match &config.snapshots {
+        Some(dir) => {
+            let nearest = snapshot::load_nearest(dir, resume_limit)?;
+            // Logging isn't set up until the app is built
+            for (path, version) in nearest.skipped {
+                eprintln!(
+                    "Skipping snapshot {}, its version is {} not {}",
+                    path.display(),
+                    version,
+                    snapshot::SNAPSHOT_VERSION
+                );
+            }
+            nearest.snapshot
+        }
+        None => None,
+    };
+    if let Some(s) = snapshot.as_ref().filter(|s| s.seed != params.universe.seed) {
+        eprintln!(
+            "Snapshot of tick {} was taken of universe {}, not {}; continuing with the snapshot",
+            s.tick, s.seed, params.universe.seed
+        );
+    }
+    let history = match snapshot {
+        // Without a journal, the snapshot is all there is to restore
+        Some(snapshot) if config.journal.is_none() => Replay {
+            snapshot: Some(snapshot),
+            events: Vec::new(),
+        },
+        Some(snapshot) => Replay {
+            events: snapshot::events_after(events, &snapshot)?,
+            snapshot: Some(snapshot),
+        },
+        None => Replay {
+            snapshot: None,
+            events,
+        },
+    };
+    let snapshot_settings = config.snapshots.clone().map(|dir| SnapshotSettings {
+        dir,
+        interval: config.snapshot_interval,
+    });
 
-use construction::construction;
-use player::{colony_commands, player_startup};
-use resources::mines;
+    // When recording, every exchange with the colonies is captured on its way through
+    let recorder = match &config.record {
+        Some(path) => {
+            if history.snapshot.is_some() || !history.events.is_empty() {
+                return Err("A resumed match can't be recorded".into());
+            }
+            Some(MatchRecorder::create(path, &params)?)
+        }
+        None => None,
+    };
+    let cinvoker = match &recorder {
+        Some(recorder) => cinvoker.wrap(|t| recorder.transport(t)),
+        None => cinvoker,
+    };
 
-fn main() -> Result<()> {
-    let nc = nats::connect("0.0.0.0").unwrap();
-    let params = GameParameters::load_from_file("./default_params.json")?; // TODO: make this a command line option
+    let mut app = App::build();
+    app.insert_resource(params)
+        .insert_resource(cinvoker)
+        .insert_resource(lobby)
+        .insert_resource(Journal(store))
+        .insert_resource(history);
+    if let Some(settings) = snapshot_settings {
+        app.insert_resource(settings);
+    }
+    if let Some(recorder) = recorder {
+        app.insert_resource(recorder);
+    }
+    if let Some(path) = config.results.clone() {
+        app.insert_resource(ResultsFile(path));
+    }
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin)
+        .add_plugin(ShardPlugin::with_step(config.tick_step()))
+        .run();
+
+    Ok(())
+}
 
-    let cinvoker = ColonyInvoker::new(nc);
+/// Plays back a recorded match without invoking any colonies, checking that every tick turns
+/// out the same as it did when it was recorded
+fn playback(path: &Path) -> Result<()> {
+    let (params, ticks) = recording::load(path)?;
+    if ticks.is_empty() {
+        return Err(format!("{} has no recorded ticks", path.display()).into());
+    }
+    let outcome = Arc::new(Mutex::new(None));
+    let (playback, transport) = Playback::new(ticks, outcome.clone());
 
     App::build()
         .insert_resource(params)
-        .insert_resource(cinvoker)
+        .insert_resource(ColonyInvoker::new(transport))
+        .insert_resource(playback)
         .add_plugins(MinimalPlugins)
         .add_plugin(LogPlugin)
-        .add_startup_system(big_bang.system().label(WasmColoniesLabels::BigBang))
-        .add_startup_system(player_startup.system().after(WasmColoniesLabels::BigBang))
-        .add_stage_before(
-            CoreStage::Update,
-            ColoniesStage::ActorRpc,
-            SystemStage::parallel()
-                .with_run_criteria(FixedTimestep::step(1.0))
-                .with_system(colony_commands.system()),
-        )
-        .add_stage(
-            ColoniesStage::Resources,
-            SystemStage::parallel()
-                .with_run_criteria(FixedTimestep::step(1.0))
-                .with_system_set(
-                    SystemSet::new()
-                        .with_system(mines.system())
-                        .with_system(construction.system()),
-                ),
-        )
+        .add_plugin(ShardPlugin::default())
+        .stage(ColoniesStage::Tick, |tick: &mut Schedule| {
+            tick.add_system_to_stage(
+                ColoniesStage::Membership,
+                playback_membership
+                    .system()
+                    .before(WasmColoniesLabels::Membership),
+            )
+            .add_system_to_stage(
+                ColoniesStage::Journal,
+                verify_tick.system().after(WasmColoniesLabels::Journal),
+            )
+        })
         .run();
 
+    let outcome = outcome.lock().unwrap().take();
+    match outcome {
+        Some(PlaybackOutcome::Matched { ticks }) => {
+            println!("All {} ticks matched the recording", ticks);
+            Ok(())
+        }
+        Some(PlaybackOutcome::Diverged { tick, reason }) => {
+            Err(format!("Playback diverged at tick {}: {}", tick, reason).into())
+        }
+        None => Err("Playback stopped before the end of the recording".into()),
+    }
+}
+
+/// Rewinds the journal to the start of the given tick, moving the events it discards to a file
+/// alongside the journal, and deletes any snapshots taken after that tick
+fn rewind(config: &ShardConfig, tick: u64) -> Result<()> {
+    let path = config
+        .journal
+        .as_ref()
+        .ok_or("Restoring a shard needs its journal")?;
+    let mut journal = FileJournal::open(path)
+        .map_err(|e| format!("Couldn't open journal {}: {}", path.display(), e))?;
+    let discarded = journal
+        .rewind(tick)
+        .map_err(|e| format!("Couldn't rewind journal {}: {}", path.display(), e))?;
+    if !discarded.is_empty() {
+        let archive = PathBuf::from(format!("{}.after-{}", path.display(), tick));
+        if archive.exists() {
+            return Err(format!("{} already exists", archive.display()).into());
+        }
+        FileJournal::open(&archive)?.append(&discarded)?;
+        eprintln!(
+            "Moved {} events from tick {} onwards to {}",
+            discarded.len(),
+            tick,
+            archive.display()
+        );
+    }
+    if let Some(dir) = &config.snapshots {
+        snapshot::discard_after(dir, tick)?;
+    }
     Ok(())
 }
@@ -0,0 +1,79 @@
+//! Movement of mobile units across the surface of their satellites
+
+use bevy::prelude::*;
+
+use crate::core::{Position, Velocity};
+use crate::procgen::Satellite;
+use crate::structure::{Structure, STRUCTURE_RADIUS};
+
+/// Fastest a colony can steer a mobile unit, in meters per tick
+pub const MAX_SPEED: f32 = 20.;
+
+/// Moves every mobile unit by its velocity. Units stop at the edge of their satellite's
+/// surface, losing the part of their velocity that carried them there, and come to a halt
+/// when they run into a structure
+pub fn movement(
+    satellites: Query<&Satellite>,
+    structures: Query<&Position, With<Structure>>,
+    mut mobiles: Query<(&mut Position, &mut Velocity), Without<Structure>>,
+) {
+    for (mut position, mut velocity) in mobiles.iter_mut() {
+        if *velocity == Velocity::default() {
+            continue;
+        }
+        let surface = match satellites
+            .iter()
+            .find(|s| s.sys == position.sys && s.sat == position.sat)
+        {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let mut target = Position {
+            x: position.x + velocity.x,
+            y: position.y + velocity.y,
+            ..position.clone()
+        };
+        if !(0. ..=surface.width).contains(&target.x) {
+            target.x = target.x.clamp(0., surface.width);
+            velocity.x = 0.;
+        }
+        if !(0. ..=surface.height).contains(&target.y) {
+            target.y = target.y.clamp(0., surface.height);
+            velocity.y = 0.;
+        }
+
+        let collision = structures
+            .iter()
+            .filter(|s| s.sys == position.sys && s.sat == position.sat)
+            .filter_map(|s| contact(&position, &target, s))
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        if let Some(t) = collision {
+            target.x = position.x + (target.x - position.x) * t;
+            target.y = position.y + (target.y - position.y) * t;
+            *velocity = Velocity::default();
+        }
+        *position = target;
+    }
+}
+
+/// How far along the path from `from` to `to`, from 0 to 1, a unit would first touch the
+/// structure at `structure`. A unit already touching the structure, or within reach of it,
+/// can move along or away from it freely
+fn contact(from: &Position, to: &Position, structure: &Position) -> Option<f32> {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let (fx, fy) = (from.x - structure.x, from.y - structure.y);
+    let a = dx * dx + dy * dy;
+    let b = 2. * (fx * dx + fy * dy);
+    let c = fx * fx + fy * fy - STRUCTURE_RADIUS * STRUCTURE_RADIUS;
+    let discriminant = b * b - 4. * a * c;
+    if a == 0. || b >= 0. || c < 0. || discriminant < 0. {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2. * a);
+    if (0. ..=1.).contains(&t) {
+        Some(t)
+    } else {
+        None
+    }
+}
@@ -1,79 +1,103 @@
 //! Player-related components and systems
 
-use crate::construction::ConstructionSite;
-use crate::resources::{Mine, ResourceType};
+use std::sync::Mutex;
+
+use crate::command::ColonyCommands;
+use crate::resources::Inventory;
 use crate::structure::PlayerBaseBundle;
-use crate::{core::Position, lattice::ColonyInvoker, rules::GameParameters, structure::Structure};
+use crate::tick::GameTick;
+use crate::view::ColonyViews;
+use crate::{
+    core::{Position, UnitId},
+    structure::Structure,
+    transport::ColonyInvoker,
+};
 use bevy::{prelude::*, tasks::ComputeTaskPool};
-use tracing::{debug, error, event, info, instrument, span, Level};
-use wasmcolonies_protocol::GameStateColonyView;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
 
 const BATCH_SIZE: usize = 10;
 
-pub fn player_startup(mut commands: Commands, game_params: Res<GameParameters>) {
-    info!(
-        "Game parameters available, {} construction times defined",
-        game_params.construction_times.len()
-    );
-    info!("Injecting initial players");
-    // Add some players to our world. Players start with a score of 0 ... we want our game to be fair!
-    let parent = commands
+/// Minimum distance between the bases of different players on the same satellite
+pub const BASE_SPACING: f32 = 100.;
+
+/// Spawns a player, holding the given inventory, along with their base, with the given id, at
+/// the given position
+pub fn spawn_player(
+    commands: &mut Commands,
+    player: Player,
+    base_id: UnitId,
+    position: Position,
+    inventory: Inventory,
+) -> Entity {
+    commands
         .spawn()
-        .insert(Player {
-            id: "player1".to_string(),
-            actor_key: "MDVVUGY5RK7TMJGJOOCOTFY6QA3M3W4ODENO43HWEKLO5OMTVKF5KAWJ".to_string(),
-        })
+        .insert(player)
+        .insert(inventory)
+        .insert(Score::default())
         .with_children(|parent| {
             parent.spawn_bundle(PlayerBaseBundle {
+                id: base_id,
                 structure: Structure::player_base(),
-                position: Position::new(0, 0, 10., 11.),
+                position,
             });
-            // TODO USE REAL VALUES
         })
-        .id();
-
-    commands
-        .spawn_bundle((
-            Mine::new(ResourceType::Wasmium, 1_000, 1_000, 1000),
-            ConstructionSite {
-                progress: 0,
-                progress_rate_pct: 1,
-            },
-        ))
-        .insert(Parent(parent));
+        .id()
 }
 
+/// Invokes every player's colony in parallel and queues the returned commands for
+/// application in the next stage, in order of player id
 pub fn colony_commands(
     pool: Res<ComputeTaskPool>,
-    mut commands: Commands,
     invoker: Res<ColonyInvoker>,
-    mut query: Query<&Player>,
+    tick: Res<GameTick>,
+    query: Query<(Entity, &Player)>,
+    views: ColonyViews,
+    mut received: EventWriter<ColonyCommands>,
 ) {
-    info!("Fetching player commands");
-    query.par_for_each(&pool, BATCH_SIZE, |player| {
+    info!("Fetching player commands for tick {}", tick.0);
+    let results = Mutex::new(Vec::new());
+    query.par_for_each(&pool, BATCH_SIZE, |(entity, player)| {
         let cmds = invoker.fetch_commands(
+            tick.0,
             &player.id,
             &player.actor_key,
-            GameStateColonyView::default(),
+            views.for_player(entity, tick.0),
         );
         match cmds {
             Ok(cmds) => {
-                info!("{:?}", cmds);
+                debug!("{:?}", cmds);
+                results.lock().unwrap().push((
+                    player.id.clone(),
+                    ColonyCommands {
+                        player: entity,
+                        commands: cmds,
+                    },
+                ));
             }
-            Err(e) => {
-                error!("{}", e);
+            Err(fault) => {
+                warn!(
+                    "Colony for player {} {}, forfeiting tick {}",
+                    player.id, fault, tick.0
+                );
             }
         }
     });
+    // Colonies answer in whatever order their threads finish, but commands are applied first
+    // come, first served, so they're applied in order of player id to keep ticks repeatable
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
+    received.send_batch(results.into_iter().map(|(_, commands)| commands));
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub actor_key: String,
     pub id: String,
 }
 
-#[derive(Default, Debug)]
+/// The points a player has scored over the course of the game
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Score {
-    pub value: usize,
+    pub value: u64,
 }
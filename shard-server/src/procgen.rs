@@ -1,6 +1,175 @@
-use crate::rules::GameParameters;
+//! Procedural generation of a shard's universe
+
+use crate::core::Position;
+use crate::rules::{GameParameters, OreSpec, UniverseParameters};
 use bevy::prelude::*;
+use rand::{
+    distributions::uniform::{SampleRange, SampleUniform},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::OreType;
+
+/// Width and height of the stellar map on which solar systems are placed, in light years
+const STAR_MAP_SIZE: f32 = 100.;
+
+/// A star and the satellites orbiting it. Coordinates are on the shard's stellar map,
+/// measured in light years
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SolarSystem {
+    pub index: u8,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SatelliteKind {
+    Planet,
+    Moon,
+    Asteroid,
+}
+
+/// A playable surface within a solar system. Positions on this satellite lie within
+/// `0..width` and `0..height`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Satellite {
+    pub sys: u8,
+    pub sat: u8,
+    pub kind: SatelliteKind,
+    /// Distance from the system's star in astronomical units
+    pub orbit: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// An untapped resource deposit on the surface of a satellite
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Deposit {
+    pub ore: OreType,
+    pub qty: u32,
+}
+
+/// The full contents of a generated universe, prior to being spawned into the world
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Universe {
+    pub seed: u64,
+    pub systems: Vec<SolarSystem>,
+    pub satellites: Vec<Satellite>,
+    pub deposits: Vec<(Deposit, Position)>,
+}
+
+/// Generates a universe from the given parameters, with deposits of the ores in the catalogue.
+/// This is a pure function of its arguments: the same seed will always produce the same universe
+pub fn generate(params: &UniverseParameters, ores: &[OreSpec]) -> Universe {
+    let mut rng = ChaCha8Rng::seed_from_u64(params.seed);
+    let mut universe = Universe {
+        seed: params.seed,
+        ..Default::default()
+    };
+
+    for sys in 0..params.solar_systems {
+        universe.systems.push(SolarSystem {
+            index: sys,
+            x: rng.gen_range(0.0..STAR_MAP_SIZE),
+            y: rng.gen_range(0.0..STAR_MAP_SIZE),
+        });
+
+        let mut orbit = 0.;
+        for sat in 0..between(&mut rng, params.satellites_per_system) {
+            let kind = match rng.gen_range(0..10) {
+                0..=4 => SatelliteKind::Planet,
+                5..=7 => SatelliteKind::Moon,
+                _ => SatelliteKind::Asteroid,
+            };
+            let scale = match kind {
+                SatelliteKind::Planet => 1.,
+                SatelliteKind::Moon => 0.5,
+                SatelliteKind::Asteroid => 0.1,
+            };
+            orbit += rng.gen_range(0.2..2.0);
+            let satellite = Satellite {
+                sys,
+                sat,
+                kind,
+                orbit,
+                width: between(&mut rng, params.surface_size) * scale,
+                height: between(&mut rng, params.surface_size) * scale,
+            };
+
+            for _ in 0..between(&mut rng, params.deposits_per_satellite) {
+                let position = Position::new(
+                    sys,
+                    sat,
+                    between(&mut rng, (0., satellite.width)),
+                    between(&mut rng, (0., satellite.height)),
+                );
+                let ore = match pick_ore(&mut rng, ores) {
+                    Some(ore) => ore,
+                    None => continue,
+                };
+                let deposit = Deposit {
+                    ore: ore.name.clone(),
+                    qty: between(&mut rng, ore.deposit_qty),
+                };
+                universe.deposits.push((deposit, position));
+            }
+            universe.satellites.push(satellite);
+        }
+    }
+
+    universe
+}
+
+/// Picks an ore at random, rarer ores being less likely to be picked
+fn pick_ore<'a>(rng: &mut ChaCha8Rng, ores: &'a [OreSpec]) -> Option<&'a OreSpec> {
+    let total: f32 = ores.iter().map(|o| 1. / o.rarity).sum();
+    if !(total > 0. && total.is_finite()) {
+        return None;
+    }
+    let mut pick = rng.gen_range(0.0..total);
+    for ore in ores {
+        pick -= 1. / ore.rarity;
+        if pick < 0. {
+            return Some(ore);
+        }
+    }
+    // Rounding can leave a sliver of the range past the last ore
+    ores.last()
+}
+
+/// Picks a value from an inclusive `(min, max)` range, tolerating empty or inverted ranges
+fn between<T>(rng: &mut ChaCha8Rng, (min, max): (T, T)) -> T
+where
+    T: SampleUniform + PartialOrd + Copy,
+    std::ops::RangeInclusive<T>: SampleRange<T>,
+{
+    if min < max {
+        rng.gen_range(min..=max)
+    } else {
+        min
+    }
+}
+
+/// Populates the world with the solar systems, satellites, and resource deposits
+/// of a universe generated from the shard's game parameters
+pub fn big_bang(mut commands: Commands, game_params: Res<GameParameters>) {
+    let universe = generate(&game_params.universe, &game_params.ores);
+    info!(
+        "Big bang (seed {}): {} solar systems, {} satellites, {} deposits",
+        universe.seed,
+        universe.systems.len(),
+        universe.satellites.len(),
+        universe.deposits.len()
+    );
 
-pub fn big_bang(mut commands: Commands, game_params: ResMut<GameParameters>) {
-    // TODO: generate the shard's universe
+    for system in universe.systems {
+        commands.spawn().insert(system);
+    }
+    for satellite in universe.satellites {
+        commands.spawn().insert(satellite);
+    }
+    for (deposit, position) in universe.deposits {
+        commands.spawn_bundle((deposit, position));
+    }
 }
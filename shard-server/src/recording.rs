@@ -0,0 +1,366 @@
+//! Recording matches, and playing them back to check the shard behaves deterministically.
+//! A match file holds the game parameters followed by one record per tick: every tick sent to
+//! a colony and its answer, the events raised, and a hash of the world once the tick finished
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use data_encoding::HEXUPPER;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{PlayerTick, PlayerTickResponse};
+
+use crate::core::Position;
+use crate::lobby::{Lobby, Membership};
+use crate::player::Player;
+use crate::rules::GameParameters;
+use crate::snapshot::WorldContents;
+use crate::tick::GameTick;
+use crate::transport::{ColonyFault, ColonyTransport};
+use crate::Result;
+
+/// Version of the match file format
+pub const MATCH_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum MatchEntry {
+    /// The first entry in every match file
+    Header {
+        version: u32,
+        params: Box<GameParameters>,
+    },
+    Tick(TickRecord),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TickRecord {
+    pub tick: u64,
+    /// Every tick sent to a colony, with its answer, ordered by actor key
+    pub exchanges: Vec<Exchange>,
+    pub events: Vec<ColonyEvent>,
+    /// Hash of the world as of the end of the tick
+    pub world_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Exchange {
+    pub actor_key: String,
+    pub request: PlayerTick,
+    pub response: std::result::Result<PlayerTickResponse, ColonyFault>,
+}
+
+/// A hash of everything in the world. Two worlds with the same hash hold the same things
+pub fn world_hash(contents: &WorldContents, tick: u64, seed: u64) -> String {
+    let snapshot = contents.capture(tick, seed);
+    // Serializing the snapshot can't fail, it's made of plain data
+    let bytes = serde_json::to_vec(&snapshot).unwrap_or_default();
+    HEXUPPER.encode(digest(&SHA256, &bytes).as_ref())
+}
+
+/// A transport that records every exchange with the colonies behind another transport
+pub struct RecordingTransport {
+    inner: Box<dyn ColonyTransport>,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl RecordingTransport {
+    pub fn new(
+        inner: Box<dyn ColonyTransport>,
+        exchanges: Arc<Mutex<Vec<Exchange>>>,
+    ) -> RecordingTransport {
+        RecordingTransport { inner, exchanges }
+    }
+}
+
+impl ColonyTransport for RecordingTransport {
+    fn player_tick(
+        &self,
+        actor_key: &str,
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault> {
+        let response = self.inner.player_tick(actor_key, tick.clone());
+        self.exchanges.lock().unwrap().push(Exchange {
+            actor_key: actor_key.to_string(),
+            request: tick,
+            response: response.clone(),
+        });
+        response
+    }
+
+    fn release(&self, actor_key: &str) {
+        self.inner.release(actor_key)
+    }
+}
+
+/// Writes a match file as the match is played
+pub struct MatchRecorder {
+    writer: Mutex<BufWriter<File>>,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl MatchRecorder {
+    /// Creates the match file, writing its header
+    pub fn create(path: &Path, params: &GameParameters) -> Result<MatchRecorder> {
+        let file = File::create(path)
+            .map_err(|e| format!("Couldn't create match file {}: {}", path.display(), e))?;
+        let recorder = MatchRecorder {
+            writer: Mutex::new(BufWriter::new(file)),
+            exchanges: Arc::new(Mutex::new(Vec::new())),
+        };
+        recorder.write(&MatchEntry::Header {
+            version: MATCH_VERSION,
+            params: Box::new(params.clone()),
+        })?;
+        Ok(recorder)
+    }
+
+    /// Wraps a transport so its exchanges are recorded in this match
+    pub fn transport(&self, inner: Box<dyn ColonyTransport>) -> RecordingTransport {
+        RecordingTransport::new(inner, self.exchanges.clone())
+    }
+
+    fn write(&self, entry: &MatchEntry) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, entry)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Records the tick that just finished
+pub fn record_tick(
+    recorder: Option<Res<MatchRecorder>>,
+    tick: Res<GameTick>,
+    params: Res<GameParameters>,
+    mut events: EventReader<ColonyEvent>,
+    contents: WorldContents,
+) {
+    let recorder = match recorder {
+        Some(r) => r,
+        None => return,
+    };
+    let finished = tick.0 - 1;
+    let mut exchanges: Vec<Exchange> = recorder.exchanges.lock().unwrap().drain(..).collect();
+    exchanges.sort_by(|a, b| a.actor_key.cmp(&b.actor_key));
+    let record = TickRecord {
+        tick: finished,
+        exchanges,
+        events: events.iter().cloned().collect(),
+        world_hash: world_hash(&contents, finished, params.universe.seed),
+    };
+    if let Err(e) = recorder.write(&MatchEntry::Tick(record)) {
+        error!("Failed to record tick {}: {}", finished, e);
+    }
+}
+
+/// Reads a match file, returning the parameters the match was played with and its ticks
+pub fn load(path: &Path) -> Result<(GameParameters, Vec<TickRecord>)> {
+    let file = File::open(path)
+        .map_err(|e| format!("Couldn't open match file {}: {}", path.display(), e))?;
+    let mut lines = BufReader::new(file).lines();
+    let params = match lines.next() {
+        Some(line) => match serde_json::from_str(&line?)? {
+            MatchEntry::Header { version, params } if version == MATCH_VERSION => *params,
+            MatchEntry::Header { version, .. } => {
+                return Err(format!(
+                    "Match file version is {}, expected {}",
+                    version, MATCH_VERSION
+                )
+                .into())
+            }
+            MatchEntry::Tick(_) => return Err("Match file has no header".into()),
+        },
+        None => return Err("Match file is empty".into()),
+    };
+    let mut ticks = Vec::new();
+    for line in lines {
+        match serde_json::from_str(&line?)? {
+            MatchEntry::Tick(record) => ticks.push(record),
+            MatchEntry::Header { .. } => return Err("Match file has a second header".into()),
+        }
+    }
+    Ok((params, ticks))
+}
+
+/// How playing back a match turned out
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackOutcome {
+    /// Every tick matched the recording
+    Matched { ticks: usize },
+    /// The playback diverged from the recording, first at the given tick
+    Diverged { tick: u64, reason: String },
+}
+
+/// A match being played back. Colonies aren't invoked; their recorded answers are used instead
+pub struct Playback {
+    ticks: HashMap<u64, TickRecord>,
+    last_tick: u64,
+    divergence: Arc<Mutex<Option<(u64, String)>>>,
+    outcome: Arc<Mutex<Option<PlaybackOutcome>>>,
+}
+
+impl Playback {
+    /// Prepares to play back the given ticks, returning the playback along with the transport
+    /// that answers for the recorded colonies. The outcome is reported through `outcome`
+    pub fn new(
+        ticks: Vec<TickRecord>,
+        outcome: Arc<Mutex<Option<PlaybackOutcome>>>,
+    ) -> (Playback, PlaybackTransport) {
+        let divergence = Arc::new(Mutex::new(None));
+        let mut responses = HashMap::new();
+        for record in &ticks {
+            for exchange in &record.exchanges {
+                responses.insert((record.tick, exchange.actor_key.clone()), exchange.clone());
+            }
+        }
+        let playback = Playback {
+            last_tick: ticks.iter().map(|t| t.tick).max().unwrap_or(0),
+            ticks: ticks.into_iter().map(|t| (t.tick, t)).collect(),
+            divergence: divergence.clone(),
+            outcome,
+        };
+        (
+            playback,
+            PlaybackTransport {
+                responses,
+                divergence,
+            },
+        )
+    }
+}
+
+/// Answers ticks with the responses recorded in a match, noting any tick that differs from
+/// what was recorded
+pub struct PlaybackTransport {
+    responses: HashMap<(u64, String), Exchange>,
+    divergence: Arc<Mutex<Option<(u64, String)>>>,
+}
+
+impl PlaybackTransport {
+    fn diverge(&self, tick: u64, reason: String) {
+        let mut divergence = self.divergence.lock().unwrap();
+        if divergence.as_ref().map(|(t, _)| tick < *t).unwrap_or(true) {
+            *divergence = Some((tick, reason));
+        }
+    }
+}
+
+impl ColonyTransport for PlaybackTransport {
+    fn player_tick(
+        &self,
+        actor_key: &str,
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault> {
+        let number = tick.tick;
+        match self.responses.get(&(number, actor_key.to_string())) {
+            Some(exchange) => {
+                if exchange.request != tick {
+                    self.diverge(
+                        number,
+                        format!("colony {} was sent a different tick", actor_key),
+                    );
+                }
+                exchange.response.clone()
+            }
+            None => {
+                self.diverge(
+                    number,
+                    format!("colony {} wasn't sent this tick originally", actor_key),
+                );
+                Err(ColonyFault::Unavailable("not in the recording".to_string()))
+            }
+        }
+    }
+}
+
+/// Queues the players who joined or left during the current tick of the recording, so they do
+/// so again at the same tick and place
+pub fn playback_membership(playback: Res<Playback>, tick: Res<GameTick>, lobby: Res<Lobby>) {
+    let record = match playback.ticks.get(&tick.0) {
+        Some(r) => r,
+        None => return,
+    };
+    let sender = lobby.sender();
+    for event in &record.events {
+        let change = match event {
+            ColonyEvent::PlayerJoined {
+                player_id,
+                actor_key,
+                location,
+                ..
+            } => Membership::Join {
+                player: Player {
+                    id: player_id.clone(),
+                    actor_key: actor_key.clone(),
+                },
+                start: Some(Position::from(location)),
+            },
+            ColonyEvent::PlayerLeft { player_id, .. } => Membership::Leave {
+                player_id: player_id.clone(),
+                actor_key: None,
+            },
+            _ => continue,
+        };
+        let _ = sender.send(change);
+    }
+}
+
+/// Compares the tick that just finished with the recording, stopping the playback at the first
+/// divergence or once the recording runs out
+pub fn verify_tick(
+    playback: Res<Playback>,
+    tick: Res<GameTick>,
+    params: Res<GameParameters>,
+    mut events: EventReader<ColonyEvent>,
+    contents: WorldContents,
+    mut exit: EventWriter<AppExit>,
+) {
+    let finished = tick.0 - 1;
+    let outcome = match playback.ticks.get(&finished) {
+        Some(record) => {
+            let events: Vec<ColonyEvent> = events.iter().cloned().collect();
+            let hash = world_hash(&contents, finished, params.universe.seed);
+            if let Some((tick, reason)) = playback.divergence.lock().unwrap().take() {
+                Some(PlaybackOutcome::Diverged { tick, reason })
+            } else if events != record.events {
+                Some(PlaybackOutcome::Diverged {
+                    tick: finished,
+                    reason: format!(
+                        "events differ, recorded {:?} but played {:?}",
+                        record.events, events
+                    ),
+                })
+            } else if hash != record.world_hash {
+                Some(PlaybackOutcome::Diverged {
+                    tick: finished,
+                    reason: format!(
+                        "world hash differs, recorded {} but played {}",
+                        record.world_hash, hash
+                    ),
+                })
+            } else if finished >= playback.last_tick {
+                Some(PlaybackOutcome::Matched {
+                    ticks: playback.ticks.len(),
+                })
+            } else {
+                None
+            }
+        }
+        None => Some(PlaybackOutcome::Diverged {
+            tick: finished,
+            reason: "the recording has no such tick".to_string(),
+        }),
+    };
+
+    if let Some(outcome) = outcome {
+        *playback.outcome.lock().unwrap() = Some(outcome);
+        exit.send(AppExit);
+    }
+}
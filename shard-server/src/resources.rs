@@ -1,15 +1,17 @@
+use std::collections::{BTreeMap, HashMap};
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::{Location, MineView, OreType};
 
 use crate::construction::ConstructionSite;
 
-#[derive(Debug, Clone)]
-pub enum ResourceType {
-    Wasmium,
-}
+/// Storage capacity of a newly constructed mine
+pub const MINE_MAX_QTY: u32 = 1_000;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mine {
-    resource_type: ResourceType,
+    ore: OreType,
     /// The total amount of this resource that exists in the "resource deposit" underlying this mine
     deposit_qty: u32,
     /// Maximum amount of this resource this mine can hold at any time
@@ -21,20 +23,110 @@ pub struct Mine {
 }
 
 impl Mine {
-    pub fn new(
-        resource_type: ResourceType,
-        max_qty: u32,
-        deposit_qty: u32,
-        yield_rate_ups: u32,
-    ) -> Mine {
+    pub fn new(ore: OreType, max_qty: u32, deposit_qty: u32, yield_rate_ups: u32) -> Mine {
         Mine {
             deposit_qty,
-            resource_type,
+            ore,
             max_qty,
             current_qty: 0,
             yield_rate_ups,
         }
     }
+
+    pub fn ore(&self) -> &OreType {
+        &self.ore
+    }
+
+    /// Amount of this resource available for pickup from the mine
+    pub fn current_qty(&self) -> u32 {
+        self.current_qty
+    }
+
+    /// Maximum amount of this resource the mine can hold
+    pub fn max_qty(&self) -> u32 {
+        self.max_qty
+    }
+
+    /// Amount of this resource left in the deposit underlying the mine
+    pub fn deposit_qty(&self) -> u32 {
+        self.deposit_qty
+    }
+
+    /// Empties the mine's stockpile, returning how much was in it
+    pub fn collect(&mut self) -> u32 {
+        std::mem::take(&mut self.current_qty)
+    }
+
+    /// Takes the given amount from the mine's stockpile, or as much of it as there is
+    pub fn withdraw(&mut self, qty: u32) {
+        self.current_qty = self.current_qty.saturating_sub(qty);
+    }
+
+    /// How this mine appears in its owner's view of the game state
+    pub fn view(&self, id: u64, location: Option<Location>) -> MineView {
+        MineView {
+            id,
+            location,
+            ore: self.ore.clone(),
+            current_qty: self.current_qty,
+            max_qty: self.max_qty,
+            deposit_qty: self.deposit_qty,
+        }
+    }
+}
+
+/// The resources a player has collected from their mines and not yet spent
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    // Ordered, so that the same inventory always serializes the same way
+    amounts: BTreeMap<OreType, u32>,
+}
+
+impl Inventory {
+    /// An inventory holding the given amounts of ore
+    pub fn with_ore(ore: &HashMap<OreType, u32>) -> Inventory {
+        let mut inventory = Inventory::default();
+        for (ore, qty) in ore {
+            inventory.add(ore.clone(), *qty);
+        }
+        inventory
+    }
+
+    pub fn amount(&self, ore: &OreType) -> u32 {
+        self.amounts.get(ore).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, ore: OreType, qty: u32) {
+        let amount = self.amounts.entry(ore).or_insert(0);
+        *amount = amount.saturating_add(qty);
+    }
+
+    /// Whether the inventory holds at least the given amount of each ore
+    pub fn can_afford(&self, cost: &HashMap<OreType, u32>) -> bool {
+        cost.iter().all(|(ore, qty)| self.amount(ore) >= *qty)
+    }
+
+    /// Takes the given amounts of ore out of the inventory. Returns false, taking nothing,
+    /// if the inventory can't afford them all
+    pub fn spend(&mut self, cost: &HashMap<OreType, u32>) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        for (ore, qty) in cost {
+            if let Some(amount) = self.amounts.get_mut(ore) {
+                *amount -= qty;
+            }
+        }
+        true
+    }
+
+    /// How the inventory appears in its owner's view of the game state
+    pub fn view(&self) -> HashMap<OreType, u32> {
+        self.amounts
+            .iter()
+            .map(|(ore, qty)| (ore.clone(), *qty))
+            .collect()
+    }
 }
 
 /// A mine is a component that will gradually store a resource that has been extracted from
@@ -42,7 +134,7 @@ impl Mine {
 // and is converted into a mine, hence the tracking of the original deposit quantity
 pub fn mines(mut query: Query<(&mut Mine, &ConstructionSite)>) {
     for (mut mine, site) in query.iter_mut() {
-        if site.progress < 100 {
+        if !site.is_complete() {
             continue;
         }
         if mine.current_qty < mine.max_qty {
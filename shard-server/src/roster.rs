@@ -0,0 +1,102 @@
+//! The roster of players admitted to a shard when it starts
+
+use std::path::{Path, PathBuf};
+
+use crate::admission::{self, AdmissionPolicy};
+use crate::core::Position;
+use crate::player::Player;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// A list of players, read from a JSON or YAML file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Roster {
+    pub players: Vec<RosterEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RosterEntry {
+    pub id: String,
+    /// The colony's actor key. When a module is given, the key is taken from the module's
+    /// claims and, if also given here, must match them
+    #[serde(default)]
+    pub actor_key: Option<String>,
+    /// The signed colony module. Required when colonies are run by the embedded host
+    #[serde(default)]
+    pub module: Option<PathBuf>,
+    /// Where the player's base should be placed. If this is missing, or isn't free, the base
+    /// is placed wherever there's room
+    #[serde(default)]
+    pub start: Option<Position>,
+}
+
+impl Roster {
+    /// Loads a roster, treating files with a `.yaml` or `.yml` extension as YAML and
+    /// everything else as JSON
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Roster> {
+        let path = path.as_ref();
+        let raw = std::fs::read(path)
+            .map_err(|e| format!("Couldn't read roster {}: {}", path.display(), e))?;
+        let yaml = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let roster = if yaml {
+            serde_yaml::from_slice(&raw).map_err(|e| format!("{}", e))
+        } else {
+            serde_json::from_slice(&raw).map_err(|e| format!("{}", e))
+        };
+        Ok(roster.map_err(|e| format!("Invalid roster {}: {}", path.display(), e))?)
+    }
+
+    /// Admits the players on the roster, on a lattice if `lattice` is set, verifying each
+    /// colony module
+    pub fn admit(self, policy: &AdmissionPolicy, lattice: bool) -> Admissions {
+        let mut admissions = Admissions::default();
+        for entry in self.players {
+            let admitted = match (&entry.module, &entry.actor_key) {
+                (Some(path), expected) => std::fs::read(path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+                    .and_then(|module| {
+                        let claims = admission::verify_module(policy, &module)
+                            .map_err(|e| format!("{}", e))?;
+                        match expected {
+                            Some(key) if *key != claims.subject => Err(format!(
+                                "module {} belongs to actor {}, not {}",
+                                path.display(),
+                                claims.subject,
+                                key
+                            )),
+                            _ => Ok((claims.subject, Some(module))),
+                        }
+                    }),
+                // The roster is trusted, so on a lattice a key is all that's needed
+                (None, Some(key)) if lattice => Ok((key.clone(), None)),
+                (None, Some(_)) => Err("the embedded host needs a colony module".to_string()),
+                (None, None) => Err("no colony module or actor key given".to_string()),
+            };
+            match admitted {
+                Ok((actor_key, module)) => admissions.admitted.push((
+                    Player {
+                        id: entry.id,
+                        actor_key,
+                    },
+                    entry.start,
+                    module,
+                )),
+                Err(e) => admissions.rejected.push((entry.id, e)),
+            }
+        }
+        admissions
+    }
+}
+
+/// The players admitted from a roster, and those refused
+#[derive(Debug, Default)]
+pub struct Admissions {
+    /// Every admitted player along with where they'd like to start and, if one was given,
+    /// their colony's module
+    pub admitted: Vec<(Player, Option<Position>, Option<Vec<u8>>)>,
+    /// Every refused player's id, along with the reason they were refused
+    pub rejected: Vec<(String, String)>,
+}
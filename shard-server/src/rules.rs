@@ -1,35 +1,571 @@
 //! Data-driven game parameters and settings
 
-use std::{collections::HashMap, fs::File, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, fs::File, path::Path, time::Duration};
 
-use crate::core::UnitType;
 use crate::Result;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::io::Read;
+use wasmcolonies_domain::{BuildRule, ConstructionRules};
+use wasmcolonies_protocol::{OreType, UnitKind};
+
+use crate::structure::{RepairRate, Structure};
 
 #[derive(Clone, Debug, PartialEq, Hash, Eq, StageLabel)]
 pub enum ColoniesStage {
+    /// The schedule holding every other stage, which plays one tick each time it runs
+    Tick,
+    Membership,
     ActorRpc,
+    Commands,
+    Movement,
     Resources,
+    Scoring,
+    EndOfTick,
+    Journal,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, SystemLabel)]
 pub enum WasmColoniesLabels {
     BigBang,
+    Membership,
+    Construction,
+    Scoring,
+    Scoreboard,
+    Journal,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameParameters {
-    pub construction_times: HashMap<UnitType, u16>,
+    /// Ticks it takes to build each type of unit. Units without a time can't be built
+    pub construction_times: HashMap<UnitKind, u16>,
+    /// Ore spent to build each type of unit. Units without a cost are free
+    #[serde(default)]
+    pub construction_costs: HashMap<UnitKind, HashMap<OreType, u32>>,
+    /// Ore in each player's inventory when they join
+    #[serde(default)]
+    pub starting_inventory: HashMap<OreType, u32>,
+    /// Every ore that can be found in the universe
+    #[serde(default = "default_ores")]
+    pub ores: Vec<OreSpec>,
+    #[serde(default)]
+    pub universe: UniverseParameters,
+    #[serde(default)]
+    pub colony_limits: ColonyLimits,
+    #[serde(default)]
+    pub travel: TravelParameters,
+    /// How each kind of weapon fights
+    #[serde(default = "default_weapons")]
+    pub weapons: HashMap<UnitKind, WeaponSpec>,
+    #[serde(default)]
+    pub repair: RepairParameters,
+    /// The levels a player base can be upgraded to beyond the first, in order
+    #[serde(default = "default_base_upgrades")]
+    pub base_upgrades: Vec<BaseUpgrade>,
+    /// The points colonies score for what they do
+    #[serde(default)]
+    pub scoring: ScoringParameters,
+    /// When the match starts and how it's won
+    #[serde(default, rename = "match")]
+    pub match_rules: MatchParameters,
+}
+
+/// The serde defaults for every field. Construction times have none, and are left empty
+impl Default for GameParameters {
+    fn default() -> GameParameters {
+        GameParameters {
+            construction_times: HashMap::new(),
+            construction_costs: HashMap::new(),
+            starting_inventory: HashMap::new(),
+            ores: default_ores(),
+            universe: UniverseParameters::default(),
+            colony_limits: ColonyLimits::default(),
+            travel: TravelParameters::default(),
+            weapons: default_weapons(),
+            repair: RepairParameters::default(),
+            base_upgrades: default_base_upgrades(),
+            scoring: ScoringParameters::default(),
+            match_rules: MatchParameters::default(),
+        }
+    }
+}
+
+/// The resources each colony may use to answer a single tick. Fuel and memory limits only
+/// apply to colonies run by the embedded host; colonies on a lattice are held to the deadline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ColonyLimits {
+    /// Fuel available per tick. Most wasm instructions consume one unit of fuel
+    pub fuel: u64,
+    /// Maximum size of a colony's linear memory, in 64KiB wasm pages
+    pub memory_pages: u32,
+    /// Wall-clock time a colony has to answer a tick
+    pub deadline_millis: u64,
+}
+
+impl Default for ColonyLimits {
+    fn default() -> ColonyLimits {
+        ColonyLimits {
+            fuel: 10_000_000,
+            memory_pages: 160,
+            deadline_millis: 1_000,
+        }
+    }
+}
+
+impl ColonyLimits {
+    pub fn deadline(&self) -> Duration {
+        Duration::from_millis(self.deadline_millis)
+    }
+}
+
+/// An ore in the catalogue, and what it's like
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OreSpec {
+    pub name: OreType,
+    /// How rare deposits of this ore are. Each deposit is of an ore picked at random, with a
+    /// chance inversely proportional to the ore's rarity
+    pub rarity: f32,
+    /// Ore extracted each tick by a mine on a deposit of this ore
+    pub yield_rate: u32,
+    /// Quantity of ore in a deposit, as an inclusive `[min, max]` range
+    pub deposit_qty: (u32, u32),
+    /// The types of unit whose construction costs this ore
+    #[serde(default)]
+    pub used_by: Vec<UnitKind>,
+}
+
+/// The catalogue used by game parameters that don't have one
+fn default_ores() -> Vec<OreSpec> {
+    vec![OreSpec {
+        name: OreType::new("Wasmium"),
+        rarity: 1.,
+        yield_rate: 10,
+        deposit_qty: (1_000, 50_000),
+        used_by: vec![UnitKind::Mine],
+    }]
+}
+
+/// How a kind of weapon fights
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WeaponSpec {
+    /// Damage done by each hit on an unarmored structure. Armor reduces it, e.g. an armor
+    /// rating of 100 halves it
+    pub damage: u16,
+    /// Furthest the weapon can hit a structure on the same satellite, in meters
+    pub range: f32,
+    /// Ticks after firing before the weapon can fire again
+    pub reload_ticks: u64,
+}
+
+/// The weapons used by game parameters that don't list any
+fn default_weapons() -> HashMap<UnitKind, WeaponSpec> {
+    let mut weapons = HashMap::new();
+    weapons.insert(
+        UnitKind::Laser,
+        WeaponSpec {
+            damage: 20,
+            range: 50.,
+            reload_ticks: 1,
+        },
+    );
+    weapons.insert(
+        UnitKind::Cannon,
+        WeaponSpec {
+            damage: 150,
+            range: 200.,
+            reload_ticks: 10,
+        },
+    );
+    weapons
+}
+
+/// Makes the construction rules set by the game parameters available as a resource, so they're
+/// only worked out once
+pub fn load_construction_rules(mut commands: Commands, params: Res<GameParameters>) {
+    commands.insert_resource(params.construction_rules());
+}
+
+/// Makes the repair rate set by the game parameters available as a resource
+pub fn load_repair_rate(mut commands: Commands, params: Res<GameParameters>) {
+    commands.insert_resource(RepairRate(params.repair.hp_per_tick));
+}
+
+/// How damaged structures are repaired
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RepairParameters {
+    /// HP restored to a structure under repair each tick
+    pub hp_per_tick: u16,
+    /// Ore spent on each HP restored, paid when the repair is ordered
+    pub cost_per_hp: HashMap<OreType, u32>,
+}
+
+impl Default for RepairParameters {
+    fn default() -> RepairParameters {
+        let mut cost_per_hp = HashMap::new();
+        cost_per_hp.insert(OreType::new("Wasmium"), 1);
+        RepairParameters {
+            hp_per_tick: 10,
+            cost_per_hp,
+        }
+    }
+}
+
+impl RepairParameters {
+    /// The ore it costs to repair the given HP
+    pub fn cost(&self, hp: u16) -> HashMap<OreType, u32> {
+        self.cost_per_hp
+            .iter()
+            .map(|(ore, per_hp)| (ore.clone(), per_hp * hp as u32))
+            .collect()
+    }
+}
+
+/// A level a player base can be upgraded to, and what the upgrade costs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BaseUpgrade {
+    pub max_hp: u16,
+    /// Armor rating
+    pub ar: u8,
+    pub cost: HashMap<OreType, u32>,
+}
+
+/// The base upgrades used by game parameters that don't list any. They're paid for in
+/// Wasmium, the only ore in the default catalogue
+fn default_base_upgrades() -> Vec<BaseUpgrade> {
+    let mut second = HashMap::new();
+    second.insert(OreType::new("Wasmium"), 300);
+    let mut third = HashMap::new();
+    third.insert(OreType::new("Wasmium"), 1_000);
+    vec![
+        BaseUpgrade {
+            max_hp: 1_500,
+            ar: 150,
+            cost: second,
+        },
+        BaseUpgrade {
+            max_hp: 2_500,
+            ar: 175,
+            cost: third,
+        },
+    ]
+}
+
+/// The points colonies score for what they do
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScoringParameters {
+    /// Points for each unit of ore a colony collects from its mines into its inventory, by
+    /// ore. Ores that aren't listed score nothing
+    #[serde(alias = "ore_mined")]
+    pub ore_collected: HashMap<OreType, u32>,
+    /// Points for each unit a colony builds, by kind, awarded once construction completes
+    pub units_built: HashMap<UnitKind, u32>,
+    /// Points for each of another colony's structures a colony destroys
+    pub structure_destroyed: u32,
+}
+
+impl Default for ScoringParameters {
+    fn default() -> ScoringParameters {
+        // A point for each unit of every ore in the default catalogue
+        let ore_collected = default_ores().into_iter().map(|o| (o.name, 1)).collect();
+        let mut units_built = HashMap::new();
+        units_built.insert(UnitKind::Mine, 10);
+        units_built.insert(UnitKind::Laser, 10);
+        units_built.insert(UnitKind::Cannon, 25);
+        ScoringParameters {
+            ore_collected,
+            units_built,
+            structure_destroyed: 500,
+        }
+    }
+}
+
+/// When a match starts and how it's won
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MatchParameters {
+    /// Players who must have joined before the match starts, at least 1. Until then, ticks
+    /// pass without colonies being invoked
+    pub min_players: usize,
+    /// How the match is won. Without one, the match never ends
+    pub win_condition: Option<WinCondition>,
+}
+
+impl Default for MatchParameters {
+    fn default() -> MatchParameters {
+        MatchParameters {
+            min_players: 1,
+            win_condition: None,
+        }
+    }
+}
+
+/// How a match is won, ending it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum WinCondition {
+    /// The last player with a base left wins
+    LastBaseStanding,
+    /// The first player to reach the given score wins
+    FirstToScore(u64),
+    /// The player with the highest score once the given tick has been played wins
+    HighestScoreAt(u64),
+}
+
+/// Parameters that shape the procedurally generated universe of a shard. The same
+/// parameters (including the seed) will always produce the same universe. Ranges
+/// are inclusive `[min, max]` pairs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct UniverseParameters {
+    pub seed: u64,
+    pub solar_systems: u8,
+    pub satellites_per_system: (u8, u8),
+    /// Width and height of the largest satellite surfaces (planets)
+    pub surface_size: (f32, f32),
+    pub deposits_per_satellite: (u8, u8),
+}
+
+impl Default for UniverseParameters {
+    fn default() -> UniverseParameters {
+        UniverseParameters {
+            seed: 0,
+            solar_systems: 4,
+            satellites_per_system: (2, 8),
+            surface_size: (500., 2_000.),
+            deposits_per_satellite: (0, 6),
+        }
+    }
+}
+
+/// How long units take to travel between satellites. Travel within a solar system crosses
+/// the distance between the satellites' orbits; travel between solar systems climbs out to
+/// the star, crosses the star map and falls in to the destination's orbit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TravelParameters {
+    /// Ticks spent leaving one surface and landing on another, however far apart they are
+    pub launch_ticks: u64,
+    /// Ticks to cross one astronomical unit within a solar system
+    pub ticks_per_au: f32,
+    /// Ticks to cross one light year of the star map
+    pub ticks_per_light_year: f32,
+}
+
+impl Default for TravelParameters {
+    fn default() -> TravelParameters {
+        TravelParameters {
+            launch_ticks: 10,
+            ticks_per_au: 20.,
+            ticks_per_light_year: 10.,
+        }
+    }
 }
 
 impl GameParameters {
-    pub fn load_from_file(path: &str) -> Result<GameParameters> {
-        let path = PathBuf::from_str(path)?;
-        let mut f = File::open(path)?;
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<GameParameters> {
+        let path = path.as_ref();
+        let mut f = File::open(path)
+            .map_err(|e| format!("Couldn't open game parameters {}: {}", path.display(), e))?;
         let mut buf = Vec::new();
         f.read_to_end(&mut buf)?;
-        Ok(serde_json::from_slice(&buf)?)
+        Ok(serde_json::from_slice(&buf)
+            .map_err(|e| format!("Invalid game parameters {}: {}", path.display(), e))?)
+    }
+
+    /// The construction rules set by these parameters, as followed by both the shard and the
+    /// domain aggregates
+    pub fn construction_rules(&self) -> ConstructionRules {
+        let mut rules = ConstructionRules::default();
+        for (kind, ticks) in &self.construction_times {
+            let rule = BuildRule {
+                ticks: *ticks as u64,
+                cost: self
+                    .construction_costs
+                    .get(kind)
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            rules.insert(*kind, rule);
+        }
+        rules
+    }
+
+    /// The catalogue entry for the given ore, if there's such an ore
+    pub fn ore(&self, ore: &OreType) -> Option<&OreSpec> {
+        self.ores.iter().find(|o| o.name == *ore)
+    }
+
+    /// How units of the given kind fight, if they're weapons
+    pub fn weapon(&self, kind: UnitKind) -> Option<&WeaponSpec> {
+        self.weapons.get(&kind)
+    }
+
+    /// The upgrade that takes a player base from the given level to the next, if there's a
+    /// level beyond it. Bases start at level 1
+    pub fn base_upgrade(&self, level: u8) -> Option<&BaseUpgrade> {
+        self.base_upgrades.get((level as usize).checked_sub(1)?)
+    }
+
+    /// Returns a description of each problem with these parameters that would stop a shard
+    /// from running with them. Parameters with no problems return an empty list
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let u = &self.universe;
+        if u.solar_systems == 0 {
+            problems.push("universe.solar_systems must be at least 1".to_string());
+        }
+        if u.satellites_per_system.0 > u.satellites_per_system.1 {
+            problems.push("universe.satellites_per_system is [min, max] but min > max".to_string());
+        }
+        if !(u.surface_size.0 > 0. && u.surface_size.0 <= u.surface_size.1) {
+            problems.push("universe.surface_size must be positive with min <= max".to_string());
+        }
+        if u.deposits_per_satellite.0 > u.deposits_per_satellite.1 {
+            problems
+                .push("universe.deposits_per_satellite is [min, max] but min > max".to_string());
+        }
+        if self.ores.is_empty() {
+            problems.push("ores must list at least one ore".to_string());
+        }
+        for (i, ore) in self.ores.iter().enumerate() {
+            if self.ores[..i].iter().any(|o| o.name == ore.name) {
+                problems.push(format!("ores lists {} more than once", ore.name));
+            }
+            if !(ore.rarity > 0. && ore.rarity.is_finite()) {
+                problems.push(format!("ores: {} must have a positive rarity", ore.name));
+            }
+            if ore.deposit_qty.0 > ore.deposit_qty.1 {
+                problems.push(format!(
+                    "ores: {} deposit_qty is [min, max] but min > max",
+                    ore.name
+                ));
+            }
+        }
+        for (kind, cost) in &self.construction_costs {
+            for ore in cost.keys() {
+                match self.ore(ore) {
+                    Some(spec) if spec.used_by.contains(kind) => {}
+                    Some(_) => problems.push(format!(
+                        "construction_costs for {} costs {}, which isn't used by {}",
+                        kind, ore, kind
+                    )),
+                    None => problems.push(format!(
+                        "construction_costs for {} costs {}, which isn't in ores",
+                        kind, ore
+                    )),
+                }
+            }
+        }
+        for ore in self.starting_inventory.keys() {
+            if self.ore(ore).is_none() {
+                problems.push(format!(
+                    "starting_inventory has {}, which isn't in ores",
+                    ore
+                ));
+            }
+        }
+        if !self.construction_times.contains_key(&UnitKind::Mine) {
+            problems.push("construction_times has no time for Mine".to_string());
+        }
+        for kind in self.construction_times.keys() {
+            if *kind != UnitKind::Mine && self.weapon(*kind).is_none() {
+                problems.push(format!(
+                    "construction_times has a time for {}, which isn't in weapons",
+                    kind
+                ));
+            }
+        }
+        for (kind, weapon) in &self.weapons {
+            if *kind == UnitKind::Mine {
+                problems.push("weapons lists Mine, which isn't a weapon".to_string());
+            }
+            if !(weapon.range > 0. && weapon.range.is_finite()) {
+                problems.push(format!("weapons: {} must have a positive range", kind));
+            }
+        }
+        for (kind, ticks) in &self.construction_times {
+            if *ticks == 0 {
+                problems.push(format!(
+                    "construction_times for {} must be at least 1",
+                    kind
+                ));
+            }
+        }
+        for kind in self.construction_costs.keys() {
+            if !self.construction_times.contains_key(kind) {
+                problems.push(format!(
+                    "construction_costs has a cost for {}, which has no construction time",
+                    kind
+                ));
+            }
+        }
+        if self.colony_limits.fuel == 0 {
+            problems.push("colony_limits.fuel must be greater than 0".to_string());
+        }
+        if self.colony_limits.memory_pages == 0 {
+            problems.push("colony_limits.memory_pages must be greater than 0".to_string());
+        }
+        if self.colony_limits.deadline_millis == 0 {
+            problems.push("colony_limits.deadline_millis must be greater than 0".to_string());
+        }
+        if self.repair.hp_per_tick == 0 {
+            problems.push("repair.hp_per_tick must be at least 1".to_string());
+        }
+        for ore in self.repair.cost_per_hp.keys() {
+            if self.ore(ore).is_none() {
+                problems.push(format!(
+                    "repair.cost_per_hp has {}, which isn't in ores",
+                    ore
+                ));
+            }
+        }
+        for ore in self.scoring.ore_collected.keys() {
+            if self.ore(ore).is_none() {
+                problems.push(format!(
+                    "scoring.ore_collected has {}, which isn't in ores",
+                    ore
+                ));
+            }
+        }
+        if self.match_rules.min_players == 0 {
+            problems.push("match.min_players must be at least 1".to_string());
+        }
+        match self.match_rules.win_condition {
+            Some(WinCondition::LastBaseStanding) if self.match_rules.min_players < 2 => {
+                problems
+                    .push("match.min_players must be at least 2 for LastBaseStanding".to_string());
+            }
+            Some(WinCondition::FirstToScore(0)) => {
+                problems.push("match.win_condition FirstToScore must be at least 1".to_string());
+            }
+            _ => {}
+        }
+        let mut base = Structure::player_base();
+        for (i, upgrade) in self.base_upgrades.iter().enumerate() {
+            if upgrade.max_hp < base.max_hp() || upgrade.ar < base.ar() {
+                problems.push(format!("base_upgrades[{}] lowers a base's max_hp or ar", i));
+            }
+            for ore in upgrade.cost.keys() {
+                if self.ore(ore).is_none() {
+                    problems.push(format!(
+                        "base_upgrades[{}] costs {}, which isn't in ores",
+                        i, ore
+                    ));
+                }
+            }
+            base.upgrade(upgrade.max_hp, upgrade.ar);
+        }
+        let t = &self.travel;
+        if !(t.ticks_per_au >= 0. && t.ticks_per_au.is_finite()) {
+            problems.push("travel.ticks_per_au must be a finite number, at least 0".to_string());
+        }
+        if !(t.ticks_per_light_year >= 0. && t.ticks_per_light_year.is_finite()) {
+            problems.push(
+                "travel.ticks_per_light_year must be a finite number, at least 0".to_string(),
+            );
+        }
+        problems
     }
 }
@@ -0,0 +1,77 @@
+//! Players' scores, earned from what happens during each tick
+
+use bevy::prelude::*;
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::PlayerScore;
+
+use crate::player::{Player, Score};
+use crate::rules::{GameParameters, ScoringParameters};
+use crate::tick::GameTick;
+
+/// The player who scores for the given event, and how many points they score, if any
+pub fn points<'e>(weights: &ScoringParameters, event: &'e ColonyEvent) -> Option<(&'e str, u64)> {
+    let (player_id, points) = match event {
+        ColonyEvent::OreCollected {
+            player_id,
+            ore,
+            qty,
+            ..
+        } => (
+            player_id,
+            (*weights.ore_collected.get(ore)? as u64).saturating_mul(*qty as u64),
+        ),
+        ColonyEvent::UnitConstructed {
+            player_id, kind, ..
+        } => (player_id, *weights.units_built.get(kind)? as u64),
+        ColonyEvent::StructureDestroyed { destroyed_by, .. } => {
+            (destroyed_by, weights.structure_destroyed as u64)
+        }
+        _ => return None,
+    };
+    Some((player_id, points))
+}
+
+/// Every player's score, highest first, with ties in order of player id
+pub fn scoreboard<'a>(scores: impl Iterator<Item = (&'a Player, &'a Score)>) -> Vec<PlayerScore> {
+    let mut scoreboard: Vec<PlayerScore> = scores
+        .map(|(player, score)| PlayerScore {
+            player_id: player.id.clone(),
+            score: score.value,
+        })
+        .collect();
+    scoreboard.sort_by(|a, b| b.score.cmp(&a.score).then(a.player_id.cmp(&b.player_id)));
+    scoreboard
+}
+
+/// Awards players the points they earned with the events of the tick
+pub fn scoring(
+    params: Res<GameParameters>,
+    mut events: EventReader<ColonyEvent>,
+    mut players: Query<(&Player, &mut Score)>,
+) {
+    for event in events.iter() {
+        let (player_id, points) = match points(&params.scoring, event) {
+            Some((_, 0)) | None => continue,
+            Some(scored) => scored,
+        };
+        if let Some((_, mut score)) = players.iter_mut().find(|(p, _)| p.id == player_id) {
+            score.value = score.value.saturating_add(points);
+        }
+    }
+}
+
+/// Announces every player's score to the event log at the end of the tick
+pub fn publish_scores(
+    tick: Res<GameTick>,
+    players: Query<(&Player, &Score)>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    let scores = scoreboard(players.iter());
+    if scores.is_empty() {
+        return;
+    }
+    events.send(ColonyEvent::ScoresPublished {
+        tick: tick.0,
+        scores,
+    });
+}
@@ -0,0 +1,413 @@
+//! Periodic snapshots of the whole shard world, so restarting doesn't mean replaying the
+//! entire journal
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::{CommandQueue, SystemParam};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_domain::ColonyEvent;
+
+use crate::combat::Weapon;
+use crate::construction::ConstructionSite;
+use crate::core::{MobileBundle, Position, UnitId, UnitIds, Velocity};
+use crate::lifecycle::Match;
+use crate::player::{Player, Score};
+use crate::procgen::{Deposit, Satellite, SolarSystem};
+use crate::resources::{Inventory, Mine};
+use crate::rules::GameParameters;
+use crate::structure::Structure;
+use crate::tick::GameTick;
+use crate::travel::Transit;
+use crate::Result;
+
+/// Version of the snapshot format. Snapshots written with any other version are ignored
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Everything in the world as of the start of a tick
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub version: u32,
+    /// The tick the shard resumes at. Every tick before it has been played
+    pub tick: u64,
+    /// The universe's seed. Nothing random happens once the universe has been generated,
+    /// so this is all the RNG state the shard has
+    pub seed: u64,
+    pub systems: Vec<SolarSystem>,
+    pub satellites: Vec<Satellite>,
+    pub deposits: Vec<(Deposit, Position)>,
+    pub players: Vec<PlayerSnapshot>,
+    /// Where the match was in its lifecycle
+    pub match_state: Match,
+    /// The unit ids handed out so far
+    pub unit_ids: UnitIds,
+}
+
+/// A player and everything they own
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSnapshot {
+    pub player: Player,
+    pub inventory: Inventory,
+    pub score: Score,
+    pub structures: Vec<(UnitId, Structure, Position)>,
+    pub mines: Vec<(UnitId, Mine, ConstructionSite, Position)>,
+    /// Mobile units, and the weapon of those that are armed
+    pub mobiles: Vec<(UnitId, Velocity, Position, Option<Weapon>)>,
+    /// Mobile units travelling between satellites
+    pub transits: Vec<(UnitId, Transit, Option<Weapon>)>,
+    /// Weapons still under construction
+    pub weapon_sites: Vec<(UnitId, Weapon, ConstructionSite, Position)>,
+}
+
+/// Where snapshots are kept and how often they're taken
+#[derive(Debug, Clone)]
+pub struct SnapshotSettings {
+    pub dir: PathBuf,
+    /// Ticks between snapshots
+    pub interval: u64,
+}
+
+/// Read-only access to everything that goes into a snapshot
+#[derive(SystemParam)]
+pub struct WorldContents<'a> {
+    systems: Query<'a, &'static SolarSystem>,
+    satellites: Query<'a, &'static Satellite>,
+    deposits: Query<'a, (&'static Deposit, &'static Position)>,
+    players: Query<'a, (Entity, &'static Player, &'static Inventory, &'static Score)>,
+    structures: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Structure,
+            &'static Position,
+            &'static Parent,
+        ),
+    >,
+    mines: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Mine,
+            &'static ConstructionSite,
+            &'static Position,
+            &'static Parent,
+        ),
+    >,
+    mobiles: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Velocity,
+            &'static Position,
+            Option<&'static Weapon>,
+            &'static Parent,
+        ),
+    >,
+    transits: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Transit,
+            Option<&'static Weapon>,
+            &'static Parent,
+        ),
+    >,
+    weapon_sites: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Weapon,
+            &'static ConstructionSite,
+            &'static Position,
+            &'static Parent,
+        ),
+    >,
+    match_state: Res<'a, Match>,
+    unit_ids: Res<'a, UnitIds>,
+}
+
+impl<'a> WorldContents<'a> {
+    /// Captures the world. Everything is listed in a fixed order (players by id, units by
+    /// theirs, everything else by where it is), so the same world always produces the same
+    /// snapshot
+    pub fn capture(&self, tick: u64, seed: u64) -> Snapshot {
+        let mut players: Vec<PlayerSnapshot> = self
+            .players
+            .iter()
+            .map(|(entity, player, inventory, score)| {
+                let mut structures: Vec<_> = self
+                    .structures
+                    .iter()
+                    .filter(|(_, _, _, parent)| parent.0 == entity)
+                    .map(|(id, s, p, _)| (*id, s.clone(), p.clone()))
+                    .collect();
+                structures.sort_by_key(|s| s.0);
+                let mut mines: Vec<_> = self
+                    .mines
+                    .iter()
+                    .filter(|(_, _, _, _, parent)| parent.0 == entity)
+                    .map(|(id, m, c, p, _)| (*id, m.clone(), c.clone(), p.clone()))
+                    .collect();
+                mines.sort_by_key(|m| m.0);
+                let mut mobiles: Vec<_> = self
+                    .mobiles
+                    .iter()
+                    .filter(|(_, _, _, _, parent)| parent.0 == entity)
+                    .map(|(id, v, p, w, _)| (*id, v.clone(), p.clone(), w.cloned()))
+                    .collect();
+                mobiles.sort_by_key(|m| m.0);
+                let mut transits: Vec<_> = self
+                    .transits
+                    .iter()
+                    .filter(|(_, _, _, parent)| parent.0 == entity)
+                    .map(|(id, t, w, _)| (*id, t.clone(), w.cloned()))
+                    .collect();
+                transits.sort_by_key(|t| t.0);
+                let mut weapon_sites: Vec<_> = self
+                    .weapon_sites
+                    .iter()
+                    .filter(|(_, _, _, _, parent)| parent.0 == entity)
+                    .map(|(id, w, c, p, _)| (*id, w.clone(), c.clone(), p.clone()))
+                    .collect();
+                weapon_sites.sort_by_key(|w| w.0);
+                PlayerSnapshot {
+                    player: player.clone(),
+                    inventory: inventory.clone(),
+                    score: score.clone(),
+                    structures,
+                    mines,
+                    mobiles,
+                    transits,
+                    weapon_sites,
+                }
+            })
+            .collect();
+        players.sort_by(|a, b| a.player.id.cmp(&b.player.id));
+
+        let mut systems: Vec<SolarSystem> = self.systems.iter().cloned().collect();
+        systems.sort_by_key(|s| s.index);
+        let mut satellites: Vec<Satellite> = self.satellites.iter().cloned().collect();
+        satellites.sort_by_key(|s| (s.sys, s.sat));
+        let mut deposits: Vec<(Deposit, Position)> = self
+            .deposits
+            .iter()
+            .map(|(d, p)| (d.clone(), p.clone()))
+            .collect();
+        deposits.sort_by(|a, b| by_position(&a.1, &b.1));
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            tick,
+            seed,
+            systems,
+            satellites,
+            deposits,
+            players,
+            match_state: self.match_state.clone(),
+            unit_ids: self.unit_ids.clone(),
+        }
+    }
+}
+
+pub(crate) fn by_position(a: &Position, b: &Position) -> Ordering {
+    (a.sys, a.sat)
+        .cmp(&(b.sys, b.sat))
+        .then(a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal))
+        .then(a.y.partial_cmp(&b.y).unwrap_or(Ordering::Equal))
+}
+
+/// Takes a snapshot after every `interval` ticks, once the tick's events have been journaled
+pub fn take_snapshot(
+    settings: Option<Res<SnapshotSettings>>,
+    tick: Res<GameTick>,
+    params: Res<GameParameters>,
+    contents: WorldContents,
+) {
+    let settings = match settings {
+        Some(s) if s.interval > 0 && tick.0 % s.interval == 0 => s,
+        _ => return,
+    };
+    let snapshot = contents.capture(tick.0, params.universe.seed);
+    match save(&settings.dir, &snapshot) {
+        Ok(path) => info!("Saved snapshot {}", path.display()),
+        Err(e) => error!("Failed to save snapshot for tick {}: {}", tick.0, e),
+    }
+}
+
+/// Writes a snapshot to the given directory, returning the path of the snapshot file
+pub fn save(dir: &Path, snapshot: &Snapshot) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file_name(snapshot.tick));
+    // Written aside and then renamed, so a crash never leaves a half-written snapshot
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_vec(snapshot)?)?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+fn file_name(tick: u64) -> String {
+    format!("snapshot-{:010}.json", tick)
+}
+
+/// The tick and path of every snapshot in the directory, oldest first
+pub fn list(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let tick = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("snapshot-"))
+            .and_then(|n| n.strip_suffix(".json"))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(tick) = tick {
+            snapshots.push((tick, path));
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Just enough of a snapshot to tell which version it is, whatever else it holds
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+}
+
+/// The snapshot to resume from, if there is one, and the newer snapshots passed over for it
+#[derive(Debug, Default)]
+pub struct Nearest {
+    pub snapshot: Option<Snapshot>,
+    /// Snapshots of another version, by path, along with their version
+    pub skipped: Vec<(PathBuf, u32)>,
+}
+
+/// Loads the most recent snapshot taken at or before the given tick. Snapshots of another
+/// version are skipped
+pub fn load_nearest(dir: &Path, tick: u64) -> Result<Nearest> {
+    let mut skipped = Vec::new();
+    for (_, path) in list(dir)?.into_iter().rev().filter(|(t, _)| *t <= tick) {
+        let bytes = fs::read(&path)?;
+        let header: SnapshotHeader = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid snapshot {}: {}", path.display(), e))?;
+        if header.version != SNAPSHOT_VERSION {
+            skipped.push((path, header.version));
+            continue;
+        }
+        let snapshot = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid snapshot {}: {}", path.display(), e))?;
+        return Ok(Nearest {
+            snapshot: Some(snapshot),
+            skipped,
+        });
+    }
+    Ok(Nearest {
+        snapshot: None,
+        skipped,
+    })
+}
+
+/// Deletes every snapshot taken after the given tick, returning how many were deleted
+pub fn discard_after(dir: &Path, tick: u64) -> Result<usize> {
+    let mut discarded = 0;
+    for (_, path) in list(dir)?.into_iter().filter(|(t, _)| *t > tick) {
+        fs::remove_file(path)?;
+        discarded += 1;
+    }
+    Ok(discarded)
+}
+
+/// The journaled events that follow a snapshot, i.e. those after the end of the tick before
+/// the one the snapshot resumes at. A journal that doesn't reach that tick (one that was
+/// truncated or rewound past the snapshot) can't be replayed on top of it
+pub fn events_after(events: Vec<ColonyEvent>, snapshot: &Snapshot) -> Result<Vec<ColonyEvent>> {
+    if snapshot.tick == 0 {
+        // Nothing had happened yet, so the whole journal follows the snapshot
+        return Ok(events);
+    }
+    let last = ColonyEvent::TickFinished(snapshot.tick - 1);
+    match events.iter().position(|e| *e == last) {
+        Some(i) => Ok(events.into_iter().skip(i + 1).collect()),
+        None => Err(format!(
+            "the journal doesn't reach tick {}, where the snapshot resumes",
+            snapshot.tick
+        )
+        .into()),
+    }
+}
+
+/// Replaces the generated universe with the contents of a snapshot, returning the entity
+/// of each restored player by id
+pub fn restore(world: &mut World, snapshot: Snapshot) -> HashMap<String, Entity> {
+    let generated: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<SolarSystem>, With<Satellite>, With<Deposit>)>>()
+        .iter(world)
+        .collect();
+    for entity in generated {
+        world.despawn(entity);
+    }
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    for system in snapshot.systems {
+        commands.spawn().insert(system);
+    }
+    for satellite in snapshot.satellites {
+        commands.spawn().insert(satellite);
+    }
+    for (deposit, position) in snapshot.deposits {
+        commands.spawn_bundle((deposit, position));
+    }
+
+    let mut players = HashMap::new();
+    for ps in snapshot.players {
+        let id = ps.player.id.clone();
+        let entity = commands
+            .spawn()
+            .insert(ps.player)
+            .insert(ps.inventory)
+            .insert(ps.score)
+            .id();
+        let mut owned = Vec::new();
+        for (id, structure, position) in ps.structures {
+            owned.push(commands.spawn_bundle((id, structure, position)).id());
+        }
+        for (id, mine, site, position) in ps.mines {
+            owned.push(commands.spawn_bundle((id, mine, site, position)).id());
+        }
+        for (id, velocity, position, weapon) in ps.mobiles {
+            let mut unit = commands.spawn_bundle(MobileBundle {
+                id,
+                position,
+                velocity,
+            });
+            if let Some(weapon) = weapon {
+                unit.insert(weapon);
+            }
+            owned.push(unit.id());
+        }
+        for (id, transit, weapon) in ps.transits {
+            let mut unit = commands.spawn_bundle((id, Velocity::default(), transit));
+            if let Some(weapon) = weapon {
+                unit.insert(weapon);
+            }
+            owned.push(unit.id());
+        }
+        for (id, weapon, site, position) in ps.weapon_sites {
+            owned.push(commands.spawn_bundle((id, weapon, site, position)).id());
+        }
+        commands.entity(entity).push_children(&owned);
+        players.insert(id, entity);
+    }
+    queue.apply(world);
+    world.insert_resource(GameTick(snapshot.tick));
+    world.insert_resource(snapshot.match_state);
+    world.insert_resource(snapshot.unit_ids);
+    players
+}
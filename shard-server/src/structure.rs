@@ -1,15 +1,33 @@
 //! Structure management components and systems
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::StructureView;
 
-use crate::core::Position;
+use crate::core::{Position, UnitId};
 
-#[derive(Default, Debug, Clone)]
+/// HP restored every tick to each structure under repair, from the game parameters
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RepairRate(pub u16);
+
+/// How close a mobile unit can get to a structure's position before colliding with it
+pub const STRUCTURE_RADIUS: f32 = 10.;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Structure {
     max_hp: u16,
     hp: u16,
     attackable: bool,
     ar: u8,
+    #[serde(default = "first_level")]
+    level: u8,
+    /// HP paid for but not yet restored
+    #[serde(default)]
+    repairs: u16,
+}
+
+fn first_level() -> u8 {
+    1
 }
 
 impl Structure {
@@ -19,12 +37,106 @@ impl Structure {
             hp: 1000,
             attackable: true,
             ar: 125,
+            level: 1,
+            repairs: 0,
+        }
+    }
+
+    pub fn hp(&self) -> u16 {
+        self.hp
+    }
+
+    pub fn max_hp(&self) -> u16 {
+        self.max_hp
+    }
+
+    /// Armor rating
+    pub fn ar(&self) -> u8 {
+        self.ar
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// HP the structure is missing that isn't already being repaired
+    pub fn unrepaired(&self) -> u16 {
+        self.max_hp
+            .saturating_sub(self.hp)
+            .saturating_sub(self.repairs)
+    }
+
+    pub fn is_attackable(&self) -> bool {
+        self.attackable
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.hp == 0
+    }
+
+    /// The damage a hit of the given strength does once it gets through the structure's
+    /// armor. Each point of armor rating takes away a little more, but every hit does at
+    /// least 1 damage
+    pub fn armor(&self, strength: u16) -> u16 {
+        let damage = strength as u32 * 100 / (100 + self.ar as u32);
+        damage.max(1) as u16
+    }
+
+    /// Takes the given damage off the structure's HP, which doesn't go below 0
+    pub fn damage(&mut self, damage: u16) {
+        self.hp = self.hp.saturating_sub(damage);
+    }
+
+    /// Orders the repair of the given HP, to be restored over the coming ticks
+    pub fn repair(&mut self, hp: u16) {
+        self.repairs = self.repairs.saturating_add(hp);
+    }
+
+    /// Restores up to `hp` of the HP under repair, never beyond the structure's max HP
+    pub fn restore(&mut self, hp: u16) {
+        let restored = hp.min(self.repairs);
+        self.repairs -= restored;
+        self.hp = self.hp.saturating_add(restored).min(self.max_hp);
+        if self.hp == self.max_hp {
+            self.repairs = 0;
+        }
+    }
+
+    /// Raises the structure to its next level, with the given max HP and armor rating. The HP
+    /// it gains in max HP come with it
+    pub fn upgrade(&mut self, max_hp: u16, ar: u8) {
+        self.hp = self.hp.saturating_add(max_hp.saturating_sub(self.max_hp));
+        self.max_hp = max_hp;
+        self.ar = ar;
+        self.level += 1;
+    }
+
+    /// How this structure appears in its owner's view of the game state
+    pub fn view(&self, id: u64, position: &Position) -> StructureView {
+        StructureView {
+            id,
+            location: position.into(),
+            hp: self.hp,
+            max_hp: self.max_hp,
+            level: self.level,
+            ar: self.ar,
+            repairing: self.repairs,
+        }
+    }
+}
+
+/// Restores a little of the HP under repair on every structure being repaired
+pub fn repair(rate: Res<RepairRate>, mut query: Query<&mut Structure>) {
+    for mut structure in query.iter_mut() {
+        if structure.repairs > 0 {
+            structure.restore(rate.0);
         }
     }
 }
 
 #[derive(Default, Debug, Clone, Bundle)]
 pub struct PlayerBaseBundle {
+    pub id: UnitId,
     pub structure: Structure,
     pub position: Position,
 }
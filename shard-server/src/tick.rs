@@ -0,0 +1,15 @@
+//! The shard-wide game clock
+
+use bevy::prelude::*;
+use wasmcolonies_domain::ColonyEvent;
+
+/// The tick currently being played. Starts at 0 and increases by one at the end of
+/// every fixed step of the game loop
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameTick(pub u64);
+
+/// Closes out the current tick, announcing it to the event log, and advances the clock
+pub fn end_of_tick(mut tick: ResMut<GameTick>, mut events: EventWriter<ColonyEvent>) {
+    events.send(ColonyEvent::TickFinished(tick.0));
+    tick.0 += 1;
+}
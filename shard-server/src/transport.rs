@@ -0,0 +1,198 @@
+//! Delivery of ticks to colonies, independent of where those colonies are running
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{
+    mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    Mutex, RwLock,
+};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::{ColonyCommand, GameStateColonyView, PlayerTick, PlayerTickResponse};
+
+/// The ways in which a colony can fail to answer a tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ColonyFault {
+    /// The colony didn't answer before its deadline
+    Timeout,
+    /// The colony used up its fuel budget for the tick
+    OutOfFuel,
+    /// The colony tried to grow its memory beyond its cap
+    OutOfMemory,
+    /// The colony's code trapped
+    Trap(String),
+    /// The colony couldn't be reached, or its answer couldn't be understood
+    Unavailable(String),
+}
+
+impl fmt::Display for ColonyFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColonyFault::Timeout => write!(f, "timed out"),
+            ColonyFault::OutOfFuel => write!(f, "ran out of fuel"),
+            ColonyFault::OutOfMemory => write!(f, "exceeded its memory limit"),
+            ColonyFault::Trap(e) => write!(f, "trapped: {}", e),
+            ColonyFault::Unavailable(e) => write!(f, "unavailable: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ColonyFault {}
+
+/// A means of delivering a tick to a colony and waiting for the colony's response
+pub trait ColonyTransport: Send + Sync {
+    fn player_tick(
+        &self,
+        actor_key: &str,
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault>;
+
+    /// Lets go of a colony whose player has left the game. Transports that don't hold on to
+    /// colonies have nothing to do
+    fn release(&self, _actor_key: &str) {}
+}
+
+/// Fetches commands from colonies over whichever transport the shard was configured with
+pub struct ColonyInvoker {
+    transport: Box<dyn ColonyTransport>,
+}
+
+impl ColonyInvoker {
+    pub fn new(transport: impl ColonyTransport + 'static) -> ColonyInvoker {
+        ColonyInvoker {
+            transport: Box::new(transport),
+        }
+    }
+
+    /// Puts another transport in front of the one this invoker uses, e.g. to observe the
+    /// traffic between the shard and its colonies
+    pub fn wrap<T, F>(self, f: F) -> ColonyInvoker
+    where
+        T: ColonyTransport + 'static,
+        F: FnOnce(Box<dyn ColonyTransport>) -> T,
+    {
+        ColonyInvoker::new(f(self.transport))
+    }
+
+    /// Asks a colony for its commands for the given tick
+    pub fn fetch_commands(
+        &self,
+        tick: u64,
+        player_id: &str,
+        actor_key: &str,
+        gs: GameStateColonyView,
+    ) -> std::result::Result<Vec<ColonyCommand>, ColonyFault> {
+        let pt = PlayerTick {
+            tick,
+            player_id: player_id.to_string(),
+            game_state: Some(gs),
+        };
+        self.transport
+            .player_tick(actor_key, pt)
+            .map(|tr| tr.commands)
+    }
+
+    /// Lets go of the colony for a player who has left the game
+    pub fn release(&self, actor_key: &str) {
+        self.transport.release(actor_key)
+    }
+}
+
+/// A tick delivered to a colony registered with a [`LocalTransport`](LocalTransport)
+pub struct LocalInvocation {
+    pub tick: PlayerTick,
+    reply: Sender<std::result::Result<PlayerTickResponse, ColonyFault>>,
+}
+
+impl LocalInvocation {
+    /// Sends the colony's response back to the shard
+    pub fn respond(self, response: PlayerTickResponse) {
+        // The shard may have given up waiting, in which case the response is dropped
+        let _ = self.reply.send(Ok(response));
+    }
+
+    /// Reports that the colony failed to handle the tick
+    pub fn fail(self, fault: ColonyFault) {
+        let _ = self.reply.send(Err(fault));
+    }
+}
+
+/// An in-process transport backed by channels, for running a shard without a lattice. Each
+/// registered colony receives its ticks as [`LocalInvocation`](LocalInvocation)s
+pub struct LocalTransport {
+    colonies: RwLock<HashMap<String, Mutex<Sender<LocalInvocation>>>>,
+    deadline: Duration,
+}
+
+impl LocalTransport {
+    /// Creates a transport that waits up to `deadline` for each colony's answer
+    pub fn new(deadline: Duration) -> LocalTransport {
+        LocalTransport {
+            colonies: RwLock::new(HashMap::new()),
+            deadline,
+        }
+    }
+
+    /// Registers a colony under the given actor key, returning the receiving end of the
+    /// channel on which its ticks will be delivered
+    pub fn register(&self, actor_key: &str) -> Receiver<LocalInvocation> {
+        let (tx, rx) = channel();
+        self.colonies
+            .write()
+            .unwrap()
+            .insert(actor_key.to_string(), Mutex::new(tx));
+        rx
+    }
+
+    /// Registers a colony whose ticks are answered by the given function on a dedicated thread
+    pub fn register_fn<F>(&self, actor_key: &str, f: F)
+    where
+        F: Fn(PlayerTick) -> PlayerTickResponse + Send + 'static,
+    {
+        let rx = self.register(actor_key);
+        thread::spawn(move || {
+            for inv in rx {
+                let response = f(inv.tick.clone());
+                inv.respond(response);
+            }
+        });
+    }
+
+    /// Removes a colony. Its receiver will see the channel close
+    pub fn unregister(&self, actor_key: &str) {
+        self.colonies.write().unwrap().remove(actor_key);
+    }
+}
+
+impl ColonyTransport for LocalTransport {
+    fn player_tick(
+        &self,
+        actor_key: &str,
+        tick: PlayerTick,
+    ) -> std::result::Result<PlayerTickResponse, ColonyFault> {
+        let (reply, response) = channel();
+        {
+            let colonies = self.colonies.read().unwrap();
+            let tx = colonies.get(actor_key).ok_or_else(|| {
+                ColonyFault::Unavailable(format!("no local colony registered for {}", actor_key))
+            })?;
+            tx.lock()
+                .unwrap()
+                .send(LocalInvocation { tick, reply })
+                .map_err(|_| ColonyFault::Unavailable("local colony has stopped".to_string()))?;
+        }
+        match response.recv_timeout(self.deadline) {
+            Ok(r) => r,
+            Err(RecvTimeoutError::Timeout) => Err(ColonyFault::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(ColonyFault::Unavailable(
+                "local colony dropped the tick".to_string(),
+            )),
+        }
+    }
+
+    fn release(&self, actor_key: &str) {
+        self.unregister(actor_key);
+    }
+}
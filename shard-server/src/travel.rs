@@ -0,0 +1,121 @@
+//! Travel between satellites, within a solar system and across the star map
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+use wasmcolonies_protocol::TransitView;
+
+use crate::core::{Position, Velocity};
+use crate::procgen::{Satellite, SolarSystem};
+use crate::rules::TravelParameters;
+
+/// A mobile unit that has left the surface of one satellite for another. A unit in transit
+/// has no position until it lands
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transit {
+    /// Where the unit launched from
+    pub origin: Position,
+    /// Where the unit will land
+    pub destination: Position,
+    elapsed: u64,
+    duration: u64,
+}
+
+impl Transit {
+    pub fn new(origin: Position, destination: Position, duration: u64) -> Transit {
+        Transit {
+            origin,
+            destination,
+            elapsed: 0,
+            duration,
+        }
+    }
+
+    pub fn has_arrived(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn ticks_remaining(&self) -> u64 {
+        self.duration.saturating_sub(self.elapsed)
+    }
+
+    /// How this transit appears in its owner's view of the game state
+    pub fn view(&self, id: u64) -> TransitView {
+        TransitView {
+            id,
+            origin: (&self.origin).into(),
+            destination: (&self.destination).into(),
+            arrives_in: self.ticks_remaining(),
+        }
+    }
+}
+
+/// Read-only access to the solar systems and satellites of the universe
+#[derive(SystemParam)]
+pub struct StarMap<'a> {
+    systems: Query<'a, &'static SolarSystem>,
+    satellites: Query<'a, &'static Satellite>,
+}
+
+impl<'a> StarMap<'a> {
+    /// The satellite with the given indices, if there is one
+    pub fn satellite(&self, sys: u8, sat: u8) -> Option<&Satellite> {
+        self.satellites
+            .iter()
+            .find(|s| s.sys == sys && s.sat == sat)
+    }
+
+    /// Ticks it takes to travel from one satellite's surface to another's, or `None` if
+    /// either satellite doesn't exist
+    pub fn travel_ticks(
+        &self,
+        params: &TravelParameters,
+        from: &Position,
+        to: &Position,
+    ) -> Option<u64> {
+        let origin = self.satellite(from.sys, from.sat)?;
+        let destination = self.satellite(to.sys, to.sat)?;
+        let au = if origin.sys == destination.sys {
+            (origin.orbit - destination.orbit).abs()
+        } else {
+            origin.orbit + destination.orbit
+        };
+        let light_years = if origin.sys == destination.sys {
+            0.
+        } else {
+            let a = self.systems.iter().find(|s| s.index == origin.sys)?;
+            let b = self.systems.iter().find(|s| s.index == destination.sys)?;
+            ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+        };
+        let crossing = au * params.ticks_per_au + light_years * params.ticks_per_light_year;
+        Some(params.launch_ticks + crossing.ceil() as u64)
+    }
+}
+
+/// Advances every unit in transit, landing those that have arrived at their destination
+pub fn transit(mut commands: Commands, mut query: Query<(Entity, &mut Transit)>) {
+    for (entity, mut transit) in query.iter_mut() {
+        transit.elapsed += 1;
+        if transit.has_arrived() {
+            commands
+                .entity(entity)
+                .remove::<Transit>()
+                .insert(transit.destination.clone());
+        }
+    }
+}
+
+/// Lifts a mobile unit off its satellite, bringing it to a stop, to land at `destination`
+/// in `duration` ticks
+pub fn launch(
+    commands: &mut Commands,
+    unit: Entity,
+    origin: &Position,
+    destination: Position,
+    duration: u64,
+) {
+    commands
+        .entity(unit)
+        .remove::<Position>()
+        .insert(Velocity::default())
+        .insert(Transit::new(origin.clone(), destination, duration));
+}
@@ -0,0 +1,196 @@
+//! Construction of each colony's view of the game world
+
+use std::collections::HashSet;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use wasmcolonies_protocol::{
+    ConstructionSiteView, DepositView, EnemyStructureView, GameStateColonyView, MobileView,
+    SatelliteView, SolarSystemView,
+};
+
+use crate::combat::{CombatReports, Weapon};
+use crate::construction::ConstructionSite;
+use crate::core::{Position, UnitId, Velocity};
+use crate::player::{Player, Score};
+use crate::procgen::{Deposit, Satellite, SolarSystem};
+use crate::resources::{Inventory, Mine};
+use crate::scoring::scoreboard;
+use crate::structure::Structure;
+use crate::travel::Transit;
+
+/// Read-only access to everything that can appear in a colony's view of the game world
+#[derive(SystemParam)]
+pub struct ColonyViews<'a> {
+    structures: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Structure,
+            &'static Position,
+            &'static Parent,
+        ),
+    >,
+    sites: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static ConstructionSite,
+            Option<&'static Position>,
+            &'static Parent,
+        ),
+    >,
+    mines: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Mine,
+            Option<&'static Position>,
+            &'static Parent,
+        ),
+    >,
+    mobiles: Query<
+        'a,
+        (
+            &'static UnitId,
+            &'static Velocity,
+            &'static Position,
+            &'static Parent,
+        ),
+    >,
+    transits: Query<'a, (&'static UnitId, &'static Transit, &'static Parent)>,
+    weapons: Query<
+        'a,
+        (
+            Entity,
+            &'static UnitId,
+            &'static Weapon,
+            Option<&'static Position>,
+            &'static Parent,
+        ),
+    >,
+    deposits: Query<'a, (&'static Deposit, &'static Position)>,
+    systems: Query<'a, &'static SolarSystem>,
+    satellites: Query<'a, &'static Satellite>,
+    inventories: Query<'a, &'static Inventory>,
+    players: Query<'a, &'static Player>,
+    scores: Query<'a, (&'static Player, &'static Score)>,
+    reports: Res<'a, CombatReports>,
+}
+
+impl<'a> ColonyViews<'a> {
+    /// Builds the view of the world belonging to the given player. A player knows about
+    /// everything it owns, the layout of the universe, the deposits and other players'
+    /// structures on any satellite where it has a presence, the attacks it was part of
+    /// during the previous tick, and every player's score
+    pub fn for_player(&self, player: Entity, tick: u64) -> GameStateColonyView {
+        let mut view = GameStateColonyView {
+            tick,
+            ..Default::default()
+        };
+        let mut satellites = HashSet::new();
+
+        for (id, structure, position, parent) in self.structures.iter() {
+            if parent.0 == player {
+                satellites.insert((position.sys, position.sat));
+                view.structures.push(structure.view(id.0, position));
+            }
+        }
+        for (id, site, position, parent) in self.sites.iter() {
+            if parent.0 == player {
+                if let Some(p) = position {
+                    satellites.insert((p.sys, p.sat));
+                }
+                view.construction_sites.push(ConstructionSiteView {
+                    id: id.0,
+                    location: position.map(|p| p.into()),
+                    progress: site.progress(),
+                });
+            }
+        }
+        for (id, mine, position, parent) in self.mines.iter() {
+            if parent.0 == player {
+                view.mines.push(mine.view(id.0, position.map(|p| p.into())));
+            }
+        }
+        for (id, velocity, position, parent) in self.mobiles.iter() {
+            if parent.0 == player {
+                satellites.insert((position.sys, position.sat));
+                view.mobiles.push(MobileView {
+                    id: id.0,
+                    location: position.into(),
+                    velocity: velocity.into(),
+                });
+            }
+        }
+        for (id, transit, parent) in self.transits.iter() {
+            if parent.0 == player {
+                view.transits.push(transit.view(id.0));
+            }
+        }
+        for (entity, id, weapon, position, parent) in self.weapons.iter() {
+            if parent.0 == player {
+                // Weapons lose their construction site once they're built
+                let ready = self.sites.get(entity).is_err();
+                view.weapons
+                    .push(weapon.view(id.0, position.map(|p| p.into()), ready));
+            }
+        }
+        for (id, structure, position, parent) in self.structures.iter() {
+            if parent.0 != player && satellites.contains(&(position.sys, position.sat)) {
+                view.enemy_structures.push(EnemyStructureView {
+                    id: id.0,
+                    player_id: self
+                        .players
+                        .get(parent.0)
+                        .map(|p| p.id.clone())
+                        .unwrap_or_default(),
+                    location: position.into(),
+                    hp: structure.hp(),
+                    max_hp: structure.max_hp(),
+                });
+            }
+        }
+        if let Ok(me) = self.players.get(player) {
+            view.combat = self
+                .reports
+                .0
+                .iter()
+                .filter(|r| r.attacker == me.id || r.defender == me.id)
+                .cloned()
+                .collect();
+        }
+        for (deposit, position) in self.deposits.iter() {
+            if satellites.contains(&(position.sys, position.sat)) {
+                view.deposits.push(DepositView {
+                    location: position.into(),
+                    ore: deposit.ore.clone(),
+                    qty: deposit.qty,
+                });
+            }
+        }
+        if let Ok(inventory) = self.inventories.get(player) {
+            view.inventory = inventory.view();
+        }
+        for system in self.systems.iter() {
+            view.systems.push(SolarSystemView {
+                index: system.index,
+                x: system.x,
+                y: system.y,
+            });
+        }
+        view.systems.sort_by_key(|s| s.index);
+        for satellite in self.satellites.iter() {
+            view.satellites.push(SatelliteView {
+                sys: satellite.sys,
+                sat: satellite.sat,
+                orbit: satellite.orbit,
+                width: satellite.width,
+                height: satellite.height,
+            });
+        }
+        view.satellites.sort_by_key(|s| (s.sys, s.sat));
+        view.scores = scoreboard(self.scores.iter());
+
+        view
+    }
+}
@@ -0,0 +1,415 @@
+//! An embedded waPC host that runs signed colony modules in-process, so a shard can be
+//! played without a wasmcloud host or a NATS lattice
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+use wasmcolonies_protocol::{deserialize, serialize, PlayerTickResponse, OP_PLAYER_TICK};
+use wasmtime::{
+    Caller, Config, Engine, Instance, InterruptHandle, Linker, Memory, Module, ResourceLimiter,
+    Store, Trap, TrapCode,
+};
+
+use crate::rules::ColonyLimits;
+use crate::transport::{ColonyFault, LocalTransport};
+use crate::Result;
+
+const HOST_NAMESPACE: &str = "wapc";
+const GUEST_CALL: &str = "__guest_call";
+const INIT_FUNCTIONS: [&str; 2] = ["_start", "wapc_init"];
+
+/// Loads colony modules and runs each of them on a dedicated thread, answering the ticks
+/// delivered through a [`LocalTransport`](LocalTransport). Every tick, each colony gets a
+/// fresh fuel budget and a deadline, and its memory can never grow past its cap
+pub struct EmbeddedHost {
+    engine: Engine,
+    limits: ColonyLimits,
+}
+
+impl EmbeddedHost {
+    pub fn new(limits: ColonyLimits) -> Result<EmbeddedHost> {
+        let mut config = Config::new();
+        config.consume_fuel(true).interruptable(true);
+        Ok(EmbeddedHost {
+            engine: Engine::new(&config)?,
+            limits,
+        })
+    }
+
+    /// Starts a colony module, registering it with the transport under the given actor key.
+    /// The module's claims must already have been verified
+    pub fn start_colony(
+        &self,
+        transport: &LocalTransport,
+        actor_key: &str,
+        module: &[u8],
+    ) -> Result<()> {
+        let module = Module::new(&self.engine, module)?;
+
+        let invocations = transport.register(actor_key);
+        let (ready_tx, ready) = channel();
+        let key = actor_key.to_string();
+        let limits = self.limits.clone();
+        thread::spawn(move || {
+            // Stores can't be shared across threads, so the instance is created on the thread
+            // that will call it
+            let mut host = match WapcHost::new(&module, limits) {
+                Ok(h) => {
+                    let _ = ready_tx.send(Ok(()));
+                    h
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(format!("{}", e)));
+                    return;
+                }
+            };
+            for inv in invocations {
+                let res = serialize(&inv.tick)
+                    .map_err(|e| ColonyFault::Unavailable(format!("{}", e)))
+                    .and_then(|payload| host.call(OP_PLAYER_TICK, &payload))
+                    .and_then(|r| {
+                        deserialize::<PlayerTickResponse>(&r)
+                            .map_err(|e| ColonyFault::Unavailable(format!("{}", e)))
+                    });
+                match res {
+                    Ok(r) => inv.respond(r),
+                    Err(fault) => {
+                        warn!("Colony {} {}", key, fault);
+                        inv.fail(fault)
+                    }
+                }
+            }
+            info!("Colony {} stopped", key);
+        });
+        ready.recv()??;
+
+        info!("Started colony {}", actor_key);
+        Ok(())
+    }
+}
+
+/// State shared between the host and the host functions linked into a guest for the
+/// duration of a single guest call
+#[derive(Default)]
+struct CallState {
+    operation: String,
+    payload: Vec<u8>,
+    response: Option<Vec<u8>>,
+    error: Option<String>,
+    host_error: Option<String>,
+}
+
+/// A single instance of a module speaking the waPC protocol
+struct WapcHost {
+    store: Store,
+    instance: Instance,
+    state: Rc<RefCell<CallState>>,
+    limits: ColonyLimits,
+    fuel_added: u64,
+    memory_denied: Rc<Cell<bool>>,
+    watchdog: Sender<Watch>,
+    /// The guest call in progress, numbered from 1, or 0 between calls
+    in_call: Arc<Mutex<u64>>,
+    calls: u64,
+}
+
+impl WapcHost {
+    fn new(module: &Module, limits: ColonyLimits) -> Result<WapcHost> {
+        let memory_denied = Rc::new(Cell::new(false));
+        let store = Store::new_with_limits(
+            module.engine(),
+            MemoryLimiter {
+                max_pages: limits.memory_pages,
+                denied: memory_denied.clone(),
+            },
+        );
+        let in_call = Arc::new(Mutex::new(0));
+        let watchdog = watchdog(
+            store.interrupt_handle()?,
+            limits.deadline(),
+            in_call.clone(),
+        );
+        let state = Rc::new(RefCell::new(CallState::default()));
+        let mut linker = Linker::new(&store);
+        link_host_functions(&mut linker, &state)?;
+
+        // Initialization is held to the same budget as a single tick
+        store.add_fuel(limits.fuel)?;
+        let instance = linker.instantiate(module)?;
+        for name in INIT_FUNCTIONS.iter() {
+            if let Some(init) = instance.get_func(name) {
+                init.typed::<(), ()>()?.call(())?;
+            }
+        }
+        Ok(WapcHost {
+            fuel_added: limits.fuel,
+            store,
+            instance,
+            state,
+            limits,
+            memory_denied,
+            watchdog,
+            in_call,
+            calls: 0,
+        })
+    }
+
+    /// Invokes an operation registered by the guest, returning the guest's response
+    fn call(
+        &mut self,
+        operation: &str,
+        payload: &[u8],
+    ) -> std::result::Result<Vec<u8>, ColonyFault> {
+        *self.state.borrow_mut() = CallState {
+            operation: operation.to_string(),
+            payload: payload.to_vec(),
+            ..Default::default()
+        };
+        self.memory_denied.set(false);
+        let refill = self.limits.fuel.saturating_sub(self.fuel_remaining());
+        self.store
+            .add_fuel(refill)
+            .map_err(|e| ColonyFault::Unavailable(format!("{}", e)))?;
+        self.fuel_added += refill;
+
+        let guest_call = self
+            .instance
+            .get_typed_func::<(i32, i32), i32>(GUEST_CALL)
+            .map_err(|e| ColonyFault::Unavailable(format!("{}", e)))?;
+        self.calls += 1;
+        *self.in_call.lock().unwrap() = self.calls;
+        let _ = self.watchdog.send(Watch::Arm(self.calls));
+        let started = Instant::now();
+        let args = (operation.len() as i32, payload.len() as i32);
+        let mut result = guest_call.call(args);
+        if let Err(trap) = &result {
+            // An interrupt that arrived just as the previous call returned is only noticed as
+            // this one enters the guest, before any of it has run, so the call is made again
+            if trap.trap_code() == Some(TrapCode::Interrupt)
+                && started.elapsed() < self.limits.deadline()
+            {
+                result = guest_call.call(args);
+            }
+        }
+        *self.in_call.lock().unwrap() = 0;
+        let _ = self.watchdog.send(Watch::Disarm);
+        let result = result.map_err(|trap| self.fault(trap))?;
+
+        let mut state = self.state.borrow_mut();
+        if result == 1 {
+            Ok(state.response.take().unwrap_or_default())
+        } else {
+            Err(ColonyFault::Trap(state.error.take().unwrap_or_else(|| {
+                format!("Guest call to {} failed", operation)
+            })))
+        }
+    }
+
+    fn fuel_remaining(&self) -> u64 {
+        self.fuel_added
+            .saturating_sub(self.store.fuel_consumed().unwrap_or_default())
+    }
+
+    /// Works out which limit, if any, caused a guest call to trap
+    fn fault(&self, trap: Trap) -> ColonyFault {
+        if trap.trap_code() == Some(TrapCode::Interrupt) {
+            ColonyFault::Timeout
+        } else if self.memory_denied.get() {
+            ColonyFault::OutOfMemory
+        } else if self.fuel_remaining() == 0 {
+            ColonyFault::OutOfFuel
+        } else {
+            ColonyFault::Trap(format!("{}", trap))
+        }
+    }
+}
+
+/// Refuses to let a guest's memory grow past its cap, remembering that it tried
+struct MemoryLimiter {
+    max_pages: u32,
+    denied: Rc<Cell<bool>>,
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(&self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        let allowed = desired <= self.max_pages;
+        if !allowed {
+            self.denied.set(true);
+        }
+        allowed
+    }
+
+    fn table_growing(&self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+}
+
+enum Watch {
+    /// Watches the guest call with the given number
+    Arm(u64),
+    Disarm,
+}
+
+/// Starts a thread that interrupts a guest if it's still running `deadline` after being armed.
+/// The guest is only interrupted if the call the watchdog was armed for is still in progress
+/// according to `in_call`, so a call that returns just as its deadline passes doesn't leave an
+/// interrupt behind for the next one
+fn watchdog(
+    handle: InterruptHandle,
+    deadline: Duration,
+    in_call: Arc<Mutex<u64>>,
+) -> Sender<Watch> {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        while let Ok(Watch::Arm(call)) = rx.recv() {
+            match rx.recv_timeout(deadline) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    if *in_call.lock().unwrap() == call {
+                        handle.interrupt();
+                    }
+                    // Wait for the interrupted call to be disarmed before arming again
+                    let _ = rx.recv();
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+    tx
+}
+
+/// Links the functions a waPC guest imports from its host. Colonies have no capabilities,
+/// so any host call made by a guest fails
+fn link_host_functions(linker: &mut Linker, state: &Rc<RefCell<CallState>>) -> Result<()> {
+    let s = state.clone();
+    linker.func(
+        HOST_NAMESPACE,
+        "__guest_request",
+        move |caller: Caller<'_>, op_ptr: i32, ptr: i32| -> std::result::Result<(), Trap> {
+            let state = s.borrow();
+            let memory = guest_memory(&caller)?;
+            write_guest(&memory, op_ptr, state.operation.as_bytes())?;
+            write_guest(&memory, ptr, &state.payload)
+        },
+    )?;
+
+    let s = state.clone();
+    linker.func(
+        HOST_NAMESPACE,
+        "__guest_response",
+        move |caller: Caller<'_>, ptr: i32, len: i32| -> std::result::Result<(), Trap> {
+            let bytes = read_guest(&guest_memory(&caller)?, ptr, len)?;
+            s.borrow_mut().response = Some(bytes);
+            Ok(())
+        },
+    )?;
+
+    let s = state.clone();
+    linker.func(
+        HOST_NAMESPACE,
+        "__guest_error",
+        move |caller: Caller<'_>, ptr: i32, len: i32| -> std::result::Result<(), Trap> {
+            let bytes = read_guest(&guest_memory(&caller)?, ptr, len)?;
+            s.borrow_mut().error = Some(String::from_utf8_lossy(&bytes).to_string());
+            Ok(())
+        },
+    )?;
+
+    let s = state.clone();
+    linker.func(
+        HOST_NAMESPACE,
+        "__host_call",
+        move |caller: Caller<'_>,
+              bd_ptr: i32,
+              bd_len: i32,
+              ns_ptr: i32,
+              ns_len: i32,
+              op_ptr: i32,
+              op_len: i32,
+              _ptr: i32,
+              _len: i32|
+              -> std::result::Result<i32, Trap> {
+            let memory = guest_memory(&caller)?;
+            let binding = read_guest(&memory, bd_ptr, bd_len)?;
+            let namespace = read_guest(&memory, ns_ptr, ns_len)?;
+            let operation = read_guest(&memory, op_ptr, op_len)?;
+            s.borrow_mut().host_error = Some(format!(
+                "Host call {}:{}/{} is not available to colonies",
+                String::from_utf8_lossy(&binding),
+                String::from_utf8_lossy(&namespace),
+                String::from_utf8_lossy(&operation)
+            ));
+            Ok(0)
+        },
+    )?;
+
+    linker.func(HOST_NAMESPACE, "__host_response_len", || -> i32 { 0 })?;
+    linker.func(HOST_NAMESPACE, "__host_response", |_ptr: i32| {})?;
+
+    let s = state.clone();
+    linker.func(HOST_NAMESPACE, "__host_error_len", move || -> i32 {
+        s.borrow()
+            .host_error
+            .as_ref()
+            .map(|e| e.len() as i32)
+            .unwrap_or(0)
+    })?;
+
+    let s = state.clone();
+    linker.func(
+        HOST_NAMESPACE,
+        "__host_error",
+        move |caller: Caller<'_>, ptr: i32| -> std::result::Result<(), Trap> {
+            match &s.borrow().host_error {
+                Some(e) => write_guest(&guest_memory(&caller)?, ptr, e.as_bytes()),
+                None => Ok(()),
+            }
+        },
+    )?;
+
+    linker.func(
+        HOST_NAMESPACE,
+        "__console_log",
+        |caller: Caller<'_>, ptr: i32, len: i32| -> std::result::Result<(), Trap> {
+            let bytes = read_guest(&guest_memory(&caller)?, ptr, len)?;
+            info!("[colony] {}", String::from_utf8_lossy(&bytes));
+            Ok(())
+        },
+    )?;
+
+    Ok(())
+}
+
+fn guest_memory(caller: &Caller<'_>) -> std::result::Result<Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .ok_or_else(|| Trap::new("Guest module does not export its memory"))
+}
+
+/// Copies `len` bytes out of the guest's memory. The pointer and length come from the guest,
+/// so they're checked against its memory before anything is allocated for them
+fn read_guest(memory: &Memory, ptr: i32, len: i32) -> std::result::Result<Vec<u8>, Trap> {
+    if ptr < 0 || len < 0 || ptr as usize + len as usize > memory.data_size() {
+        return Err(Trap::new(format!(
+            "Guest read of {} bytes at {} is out of bounds",
+            len, ptr
+        )));
+    }
+    let mut buf = vec![0; len as usize];
+    memory
+        .read(ptr as usize, &mut buf)
+        .map_err(|e| Trap::new(format!("{}", e)))?;
+    Ok(buf)
+}
+
+fn write_guest(memory: &Memory, ptr: i32, bytes: &[u8]) -> std::result::Result<(), Trap> {
+    memory
+        .write(ptr as usize, bytes)
+        .map_err(|e| Trap::new(format!("{}", e)))
+}
@@ -0,0 +1,123 @@
+//! Which colonies a shard admits, going by their signed claims
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use wascap::jwt::{Actor, Claims};
+use wascap::prelude::KeyPair;
+use wcshard::admission::{verify_token, AdmissionError, AdmissionPolicy, COLONY_TAG};
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Claims for a colony module, issued by `issuer`
+fn colony_claims(issuer: &KeyPair, module: &KeyPair) -> Claims<Actor> {
+    Claims {
+        expires: None,
+        id: "colony".to_string(),
+        issued_at: now(),
+        issuer: issuer.public_key(),
+        subject: module.public_key(),
+        not_before: None,
+        metadata: Some(Actor {
+            name: Some("Colony".to_string()),
+            module_hash: String::new(),
+            tags: Some(vec![COLONY_TAG.to_string()]),
+            caps: None,
+            rev: None,
+            ver: None,
+            call_alias: None,
+            provider: false,
+        }),
+    }
+}
+
+fn trusting(issuer: &KeyPair) -> AdmissionPolicy {
+    AdmissionPolicy {
+        trusted_issuers: vec![issuer.public_key()],
+    }
+}
+
+#[test]
+fn colonies_signed_by_a_trusted_issuer_are_admitted() {
+    let issuer = KeyPair::new_account();
+    let module = KeyPair::new_module();
+    let jwt = colony_claims(&issuer, &module).encode(&issuer).unwrap();
+
+    let claims = verify_token(&trusting(&issuer), &jwt).unwrap();
+    assert_eq!(claims.subject, module.public_key());
+    // Any issuer will do when the shard doesn't name any
+    assert!(verify_token(&AdmissionPolicy::default(), &jwt).is_ok());
+}
+
+#[test]
+fn colonies_from_other_issuers_are_refused() {
+    let issuer = KeyPair::new_account();
+    let stranger = KeyPair::new_account();
+    let jwt = colony_claims(&stranger, &KeyPair::new_module())
+        .encode(&stranger)
+        .unwrap();
+    assert_eq!(
+        verify_token(&trusting(&issuer), &jwt).unwrap_err(),
+        AdmissionError::UntrustedIssuer(stranger.public_key())
+    );
+}
+
+#[test]
+fn claims_signed_by_anyone_but_their_issuer_are_refused() {
+    let issuer = KeyPair::new_account();
+    let forger = KeyPair::new_account();
+    let jwt = colony_claims(&issuer, &KeyPair::new_module())
+        .encode(&forger)
+        .unwrap();
+    assert_eq!(
+        verify_token(&trusting(&issuer), &jwt).unwrap_err(),
+        AdmissionError::BadSignature
+    );
+}
+
+#[test]
+fn claims_are_only_good_while_they_are_valid() {
+    let issuer = KeyPair::new_account();
+    let module = KeyPair::new_module();
+
+    let mut expired = colony_claims(&issuer, &module);
+    expired.expires = Some(now() - 60);
+    let jwt = expired.encode(&issuer).unwrap();
+    assert!(matches!(
+        verify_token(&trusting(&issuer), &jwt),
+        Err(AdmissionError::Expired(_))
+    ));
+
+    let mut early = colony_claims(&issuer, &module);
+    early.not_before = Some(now() + 3600);
+    let jwt = early.encode(&issuer).unwrap();
+    assert!(matches!(
+        verify_token(&trusting(&issuer), &jwt),
+        Err(AdmissionError::NotYetValid(_))
+    ));
+}
+
+#[test]
+fn modules_must_be_tagged_as_colonies() {
+    let issuer = KeyPair::new_account();
+    let mut claims = colony_claims(&issuer, &KeyPair::new_module());
+    if let Some(actor) = claims.metadata.as_mut() {
+        actor.tags = Some(vec!["something else".to_string()]);
+    }
+    let jwt = claims.encode(&issuer).unwrap();
+    assert_eq!(
+        verify_token(&trusting(&issuer), &jwt).unwrap_err(),
+        AdmissionError::NotAColony
+    );
+
+    // A colony capability will do instead of the tag
+    if let Some(actor) = claims.metadata.as_mut() {
+        actor.caps = Some(vec![COLONY_TAG.to_string()]);
+    }
+    let jwt = claims.encode(&issuer).unwrap();
+    assert!(verify_token(&trusting(&issuer), &jwt).is_ok());
+}
@@ -0,0 +1,105 @@
+//! Command line flags, and how they combine with the configuration file
+
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+use wcshard::cli::{Cli, Command, RunArgs, ShardConfig};
+
+/// The config file `config` overridden by the given `run` flags
+fn configure(config: &str, flags: &[&str]) -> ShardConfig {
+    let path = std::env::temp_dir().join(format!(
+        "wcshard-config-{}-{}.toml",
+        std::process::id(),
+        flags.join("")
+    ));
+    std::fs::write(&path, config).unwrap();
+    let mut args = vec!["wcshard", "--config", path.to_str().unwrap(), "run"];
+    args.extend(flags);
+    let cli = Cli::from_iter(args);
+    let mut shard = ShardConfig::load(cli.config.as_deref()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    match cli.cmd {
+        Command::Run(args) => shard.apply_run_args(args),
+        other => panic!("expected run, got {:?}", other),
+    }
+    shard
+}
+
+const CONFIG: &str = r#"
+seed = 42
+tick_rate = 2.0
+lattice = true
+journal = "./shard.journal"
+colonies = ["a.wasm", "b.wasm"]
+
+[admission]
+trusted_issuers = ["ISSUER_A", "ISSUER_B"]
+"#;
+
+#[test]
+fn the_example_config_is_valid() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("shard.toml");
+    let config = ShardConfig::load(Some(&path)).unwrap();
+    assert_eq!(config.snapshot_interval, 100);
+    assert!(!config.lattice);
+}
+
+#[test]
+fn settings_without_flags_come_from_the_config_file() {
+    let config = configure(CONFIG, &[]);
+    assert_eq!(config.seed, Some(42));
+    assert_eq!(config.tick_rate, 2.0);
+    assert!(config.lattice);
+    assert_eq!(config.journal, Some(PathBuf::from("./shard.journal")));
+    assert_eq!(
+        config.colonies,
+        vec![PathBuf::from("a.wasm"), PathBuf::from("b.wasm")]
+    );
+    assert_eq!(
+        config.admission.trusted_issuers,
+        vec!["ISSUER_A".to_string(), "ISSUER_B".to_string()]
+    );
+    // Anything missing from both is left at its default
+    assert_eq!(config.snapshot_interval, 100);
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let config = configure(
+        CONFIG,
+        &[
+            "--seed",
+            "7",
+            "--tick-rate",
+            "4",
+            "--no-lattice",
+            "--journal",
+            "other.journal",
+            "--trusted-issuer",
+            "ISSUER_C",
+            "c.wasm",
+        ],
+    );
+    assert_eq!(config.seed, Some(7));
+    assert_eq!(config.tick_rate, 4.0);
+    assert!(!config.lattice);
+    assert_eq!(config.journal, Some(PathBuf::from("other.journal")));
+    assert_eq!(config.colonies, vec![PathBuf::from("c.wasm")]);
+    assert_eq!(
+        config.admission.trusted_issuers,
+        vec!["ISSUER_C".to_string()]
+    );
+}
+
+#[test]
+fn the_last_of_lattice_and_no_lattice_wins() {
+    let parse = |flags: &[&str]| {
+        let mut args = vec!["run"];
+        args.extend(flags);
+        RunArgs::from_iter(args).lattice()
+    };
+    assert_eq!(parse(&[]), None);
+    assert_eq!(parse(&["--lattice"]), Some(true));
+    assert_eq!(parse(&["--lattice", "--no-lattice"]), Some(false));
+    assert_eq!(parse(&["--no-lattice", "--lattice"]), Some(true));
+}
@@ -0,0 +1,216 @@
+//! The same inputs always make the same world: generating the universe, replaying the journal,
+//! restoring snapshots and playing back recorded matches
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use wasmcolonies_protocol::{ColonyCommand, GameStateColonyView, OreType, UnitType};
+use wcshard::harness::Harness;
+use wcshard::journal::Replay;
+use wcshard::procgen::generate;
+use wcshard::recording::PlaybackOutcome;
+use wcshard::rules::GameParameters;
+use wcshard::snapshot::{self, Snapshot, SnapshotSettings};
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+/// A directory of the test's own, emptied of anything a previous run left behind
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wcshard-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A colony that mines, builds a laser and drives it around
+fn busy_colony(shard: &Harness) {
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        let mut commands = Vec::new();
+        match tick.tick {
+            1 => commands.push(ColonyCommand::ConstructUnit(
+                1,
+                UnitType::Mine(OreType::new("Wasmium")),
+            )),
+            40 => commands.push(ColonyCommand::ConstructUnit(40, UnitType::Laser)),
+            _ => {}
+        }
+        if tick.tick % 5 == 0 {
+            for mine in &view.mines {
+                commands.push(ColonyCommand::Collect(tick.tick, mine.id));
+            }
+        }
+        for mobile in &view.mobiles {
+            let heading = if tick.tick % 20 < 10 { 3. } else { -3. };
+            commands.push(ColonyCommand::Move(tick.tick, mobile.id, (heading, 1.)));
+        }
+        commands
+    });
+}
+
+/// Everything a player has, for comparing two worlds
+fn holdings(shard: &mut Harness, player_id: &str) -> String {
+    format!(
+        "{:?} {:?} {:?} {:?} {:?}",
+        shard.inventory(player_id),
+        shard.structures(player_id),
+        shard.mines(player_id),
+        shard.mobiles(player_id),
+        shard.transits(player_id),
+    )
+}
+
+fn snapshot_at(dir: &Path, tick: u64) -> Snapshot {
+    let nearest = snapshot::load_nearest(dir, tick).unwrap();
+    let snapshot = nearest.snapshot.unwrap();
+    assert_eq!(snapshot.tick, tick);
+    snapshot
+}
+
+#[test]
+fn the_same_seed_generates_the_same_universe() {
+    let params = params();
+    let universe = generate(&params.universe, &params.ores);
+    assert_eq!(universe, generate(&params.universe, &params.ores));
+    assert!(!universe.satellites.is_empty());
+
+    let mut reseeded = params.universe.clone();
+    reseeded.seed += 1;
+    assert_ne!(universe, generate(&reseeded, &params.ores));
+}
+
+#[test]
+fn replaying_the_journal_rebuilds_the_world() {
+    let mut played = Harness::new(params());
+    busy_colony(&played);
+    played.join("alice", "alice", None);
+    played.advance(70);
+    assert!(!played.mobiles("alice").is_empty());
+
+    // The colony rejoins with the journal, and carries on from where it was
+    let mut replayed = Harness::new(params());
+    busy_colony(&replayed);
+    replayed.world().insert_resource(Replay {
+        snapshot: None,
+        events: played.events(),
+    });
+    // Both shards play one more tick, the replayed one after catching up
+    played.advance(1);
+    replayed.advance(1);
+    assert_eq!(replayed.tick(), played.tick());
+    assert_eq!(
+        holdings(&mut replayed, "alice"),
+        holdings(&mut played, "alice")
+    );
+}
+
+/// The ids of every unit in a view, by the kind of unit
+fn unit_ids(view: &GameStateColonyView) -> Vec<Vec<u64>> {
+    let mut ids = vec![
+        view.structures.iter().map(|s| s.id).collect::<Vec<_>>(),
+        view.construction_sites.iter().map(|s| s.id).collect(),
+        view.mines.iter().map(|m| m.id).collect(),
+        view.mobiles.iter().map(|m| m.id).collect(),
+        view.weapons.iter().map(|w| w.id).collect(),
+    ];
+    for kind in &mut ids {
+        kind.sort_unstable();
+    }
+    ids
+}
+
+#[test]
+fn colonies_see_the_same_unit_ids_once_the_world_is_rebuilt() {
+    let mut played = Harness::new(params());
+    busy_colony(&played);
+    played.join("alice", "alice", None);
+    played.advance(70);
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut replayed = Harness::new(params());
+    replayed.world().insert_resource(Replay {
+        snapshot: None,
+        events: played.events(),
+    });
+    for shard in [&played, &replayed].iter() {
+        let seen = seen.clone();
+        shard.colonies().script("alice", move |tick| {
+            let view = tick.game_state.as_ref().unwrap();
+            seen.lock().unwrap().push(unit_ids(view));
+            Vec::new()
+        });
+    }
+    played.advance(1);
+    replayed.advance(1);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    // A base, a mine and a laser, each with an id of its own however many lists it's in
+    let mut distinct: Vec<u64> = seen[0].iter().flatten().copied().collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 3);
+    assert_eq!(seen[0], seen[1]);
+}
+
+#[test]
+fn a_restored_snapshot_plays_on_like_the_original() {
+    let dir = scratch("snapshots");
+    let restored_dir = scratch("restored-snapshots");
+    let mut played = Harness::new(params());
+    played.world().insert_resource(SnapshotSettings {
+        dir: dir.clone(),
+        interval: 25,
+    });
+    busy_colony(&played);
+    played.join("alice", "alice", None);
+    played.advance(60);
+
+    // Restore the snapshot of tick 50, and the journal from there on
+    let snapshot = snapshot_at(&dir, 50);
+    let events = snapshot::events_after(played.events(), &snapshot).unwrap();
+    let mut restored = Harness::new(params());
+    busy_colony(&restored);
+    restored.world().insert_resource(SnapshotSettings {
+        dir: restored_dir.clone(),
+        interval: 25,
+    });
+    restored.world().insert_resource(Replay {
+        snapshot: Some(snapshot),
+        events,
+    });
+    restored.advance(15);
+    played.advance(15);
+    assert_eq!(restored.tick(), 75);
+    assert_eq!(played.tick(), 75);
+
+    let original = serde_json::to_value(snapshot_at(&dir, 75)).unwrap();
+    let copy = serde_json::to_value(snapshot_at(&restored_dir, 75)).unwrap();
+    assert_eq!(original, copy);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&restored_dir);
+}
+
+#[test]
+fn a_recorded_match_plays_back_the_same() {
+    let dir = scratch("recording");
+    let path = dir.join("match.jsonl");
+    let mut recorded = Harness::recording(params(), &path).unwrap();
+    busy_colony(&recorded);
+    recorded.join("alice", "alice", None);
+    recorded.advance(60);
+    // Let go of the match file
+    drop(recorded);
+
+    let (mut playback, outcome) = Harness::playback(&path).unwrap();
+    playback.advance(60);
+    assert_eq!(
+        outcome.lock().unwrap().take(),
+        Some(PlaybackOutcome::Matched { ticks: 60 })
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
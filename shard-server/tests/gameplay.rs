@@ -0,0 +1,234 @@
+//! The rules of the game, played out on a headless shard
+
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{ColonyCommand, OreType, UnitKind, UnitType};
+use wcshard::harness::Harness;
+use wcshard::rules::GameParameters;
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+fn wasmium() -> OreType {
+    OreType::new("Wasmium")
+}
+
+#[test]
+fn mines_stockpile_ore_until_collected() {
+    let mut shard = Harness::new(params());
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        let mut commands = Vec::new();
+        if tick.tick == 1 {
+            commands.push(ColonyCommand::ConstructUnit(1, UnitType::Mine(wasmium())));
+        }
+        if tick.tick == 40 {
+            for mine in &view.mines {
+                commands.push(ColonyCommand::Collect(40, mine.id));
+            }
+        }
+        commands
+    });
+    shard.join("alice", "alice", None);
+
+    // Players join during tick 0, and the match starts with tick 1
+    shard.advance(31);
+    // The mine took all of the starting ore, and has only just been completed
+    assert_eq!(shard.inventory("alice").unwrap().amount(&wasmium()), 0);
+    let mines = shard.mines("alice");
+    assert_eq!(mines.len(), 1);
+    assert!(mines[0].1.is_complete());
+
+    // Ten ticks' worth of ore at 10 a tick, collected during tick 40
+    shard.advance(10);
+    let collected = shard.inventory("alice").unwrap().amount(&wasmium());
+    assert_eq!(collected, 10 * 10);
+    let mined: u32 = shard
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            ColonyEvent::OreCollected { qty, .. } => Some(*qty),
+            _ => None,
+        })
+        .sum();
+    assert_eq!(mined, collected);
+}
+
+#[test]
+fn construction_takes_the_time_its_rule_gives() {
+    let mut shard = Harness::new(params());
+    shard.colonies().script("alice", |tick| {
+        if tick.tick == 3 {
+            vec![ColonyCommand::ConstructUnit(3, UnitType::Laser)]
+        } else {
+            Vec::new()
+        }
+    });
+    shard.join("alice", "alice", None);
+
+    // A laser takes 20 ticks, counting the one it was begun in
+    shard.advance(22);
+    let weapons = shard.weapons("alice");
+    assert_eq!(weapons.len(), 1);
+    assert!(shard.mobiles("alice").is_empty());
+    shard.advance(1);
+    assert_eq!(shard.mobiles("alice").len(), 1);
+
+    let completed: Vec<(u64, UnitKind)> = shard
+        .events()
+        .into_iter()
+        .filter_map(|e| match e {
+            ColonyEvent::UnitConstructed { tick, kind, .. } => Some((tick, kind)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(completed, vec![(22, UnitKind::Laser)]);
+    // Cost 50 of the 100 starting Wasmium
+    assert_eq!(shard.inventory("alice").unwrap().amount(&wasmium()), 50);
+}
+
+#[test]
+fn attacks_take_hp_off_structures_through_their_armor() {
+    let mut params = params();
+    params.match_rules.min_players = 2;
+    // Out of reach otherwise, since bases are spaced well apart
+    params.weapons.get_mut(&UnitKind::Laser).unwrap().range = 10_000.;
+    let mut shard = Harness::new(params);
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        let mut commands = Vec::new();
+        if tick.tick == 1 {
+            commands.push(ColonyCommand::ConstructUnit(1, UnitType::Laser));
+        }
+        for weapon in view.weapons.iter().filter(|w| w.ready && w.reload == 0) {
+            for target in &view.enemy_structures {
+                commands.push(ColonyCommand::Attack(tick.tick, weapon.id, target.id));
+            }
+        }
+        commands
+    });
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+
+    // The laser is completed during tick 20, and fires once in each of the next three ticks
+    shard.advance(24);
+    let base = &shard.structures("bob")[0].0;
+    // 20 damage against an armor rating of 125 is 20 * 100 / 225
+    assert_eq!(base.hp(), 1000 - 3 * 8);
+    let hits: Vec<u16> = shard
+        .events()
+        .into_iter()
+        .filter_map(|e| match e {
+            ColonyEvent::StructureAttacked { damage, .. } => Some(damage),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(hits, vec![8, 8, 8]);
+}
+
+#[test]
+fn repairs_restore_hp_over_the_following_ticks() {
+    let mut params = params();
+    params.match_rules.min_players = 2;
+    params.weapons.get_mut(&UnitKind::Laser).unwrap().range = 10_000.;
+    let mut shard = Harness::new(params);
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        let mut commands = Vec::new();
+        if tick.tick == 1 {
+            commands.push(ColonyCommand::ConstructUnit(1, UnitType::Laser));
+        }
+        // Stops firing once it's done enough damage to repair
+        if tick.tick < 30 {
+            for weapon in view.weapons.iter().filter(|w| w.ready && w.reload == 0) {
+                for target in &view.enemy_structures {
+                    commands.push(ColonyCommand::Attack(tick.tick, weapon.id, target.id));
+                }
+            }
+        }
+        commands
+    });
+    shard.colonies().script("bob", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        if tick.tick == 30 {
+            let base = &view.structures[0];
+            vec![ColonyCommand::Repair(30, base.id)]
+        } else {
+            Vec::new()
+        }
+    });
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+
+    shard.advance(30);
+    let damaged = shard.structures("bob")[0].0.hp();
+    assert!(damaged < 1000);
+    // Repairs cost 1 Wasmium per HP, paid up front
+    let before = shard.inventory("bob").unwrap().amount(&wasmium());
+    shard.advance(1);
+    let paid = before - shard.inventory("bob").unwrap().amount(&wasmium());
+    assert_eq!(paid, (1000 - damaged) as u32);
+    // 10 HP are restored every tick
+    shard.advance(20);
+    assert_eq!(shard.structures("bob")[0].0.hp(), 1000);
+}
+
+/// A colony that upgrades its base in each of the given ticks
+fn upgrader(shard: &Harness, player_id: &str, ticks: &'static [u64]) {
+    shard.colonies().script(player_id, move |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        if ticks.contains(&tick.tick) {
+            vec![ColonyCommand::Upgrade(tick.tick, view.structures[0].id)]
+        } else {
+            Vec::new()
+        }
+    });
+}
+
+fn upgrades(shard: &Harness) -> Vec<(u64, u8)> {
+    shard
+        .events()
+        .into_iter()
+        .filter_map(|e| match e {
+            ColonyEvent::StructureUpgraded { tick, level, .. } => Some((tick, level)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn bases_are_upgraded_a_level_at_a_time_up_to_the_last() {
+    let mut params = params();
+    let cranelite = OreType::new("Cranelite");
+    params.starting_inventory.insert(wasmium(), 1_000);
+    params.starting_inventory.insert(cranelite.clone(), 200);
+    let mut shard = Harness::new(params);
+    upgrader(&shard, "alice", &[1, 2, 3]);
+    shard.join("alice", "alice", None);
+
+    shard.advance(2);
+    let base = shard.structures("alice")[0].0.clone();
+    assert_eq!((base.level(), base.max_hp(), base.ar()), (2, 1_500, 150));
+
+    // The third upgrade is refused, as there's no level after the last one listed
+    shard.advance(2);
+    let base = shard.structures("alice")[0].0.clone();
+    assert_eq!((base.level(), base.max_hp(), base.ar()), (3, 2_500, 175));
+    assert_eq!(upgrades(&shard), vec![(1, 2), (2, 3)]);
+    // 300 Wasmium, then 600 Wasmium and 200 Cranelite
+    let inventory = shard.inventory("alice").unwrap();
+    assert_eq!(inventory.amount(&wasmium()), 100);
+    assert_eq!(inventory.amount(&cranelite), 0);
+}
+
+#[test]
+fn upgrades_the_player_cannot_afford_are_refused() {
+    let mut shard = Harness::new(params());
+    upgrader(&shard, "alice", &[1]);
+    shard.join("alice", "alice", None);
+
+    shard.advance(2);
+    assert_eq!(shard.structures("alice")[0].0.level(), 1);
+    assert!(upgrades(&shard).is_empty());
+    assert_eq!(shard.inventory("alice").unwrap().amount(&wasmium()), 100);
+}
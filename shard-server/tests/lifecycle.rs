@@ -0,0 +1,146 @@
+//! Matches: waiting in the lobby for players, and being won
+
+use std::sync::{Arc, Mutex};
+
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{ColonyCommand, OreType, UnitKind, UnitType};
+use wcshard::harness::Harness;
+use wcshard::lifecycle::{Match, MatchPhase, MatchResult, ResultsFile};
+use wcshard::rules::{GameParameters, WinCondition};
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+fn with_rules(min_players: usize, win_condition: WinCondition) -> GameParameters {
+    let mut params = params();
+    params.match_rules.min_players = min_players;
+    params.match_rules.win_condition = Some(win_condition);
+    params
+}
+
+/// The tick the match started in, and its players
+fn started(shard: &Harness) -> Option<(u64, Vec<String>)> {
+    shard.events().into_iter().find_map(|e| match e {
+        ColonyEvent::MatchStarted { tick, players } => Some((tick, players)),
+        _ => None,
+    })
+}
+
+/// The tick the match was decided in, and its winners
+fn ended(shard: &Harness) -> Option<(u64, Vec<String>)> {
+    shard.events().into_iter().find_map(|e| match e {
+        ColonyEvent::MatchEnded { tick, winners, .. } => Some((tick, winners)),
+        _ => None,
+    })
+}
+
+fn names(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn matches_wait_in_the_lobby_for_enough_players() {
+    let mut shard = Harness::new(with_rules(2, WinCondition::HighestScoreAt(1_000)));
+    let invoked = Arc::new(Mutex::new(0));
+    let count = invoked.clone();
+    shard.colonies().script("alice", move |_| {
+        *count.lock().unwrap() += 1;
+        Vec::new()
+    });
+    shard.join("alice", "alice", None);
+    shard.advance(5);
+    assert_eq!(started(&shard), None);
+    assert_eq!(*invoked.lock().unwrap(), 0);
+
+    // Bob joins during tick 5, and the match starts with the next one
+    shard.join("bob", "bob", None);
+    shard.advance(2);
+    assert_eq!(started(&shard), Some((6, names(&["alice", "bob"]))));
+    assert_eq!(*invoked.lock().unwrap(), 1);
+}
+
+#[test]
+fn the_last_base_standing_wins() {
+    let mut params = with_rules(2, WinCondition::LastBaseStanding);
+    // One hit from a laser anywhere on the satellite destroys a base
+    let laser = params.weapons.get_mut(&UnitKind::Laser).unwrap();
+    laser.damage = 5_000;
+    laser.range = 10_000.;
+    let mut shard = Harness::new(params);
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        let mut commands = Vec::new();
+        if tick.tick == 1 {
+            commands.push(ColonyCommand::ConstructUnit(1, UnitType::Laser));
+        }
+        for weapon in view.weapons.iter().filter(|w| w.ready) {
+            for target in &view.enemy_structures {
+                commands.push(ColonyCommand::Attack(tick.tick, weapon.id, target.id));
+            }
+        }
+        commands
+    });
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+
+    // The laser is completed during tick 20, and fires during tick 21
+    shard.advance(21);
+    assert_eq!(ended(&shard), None);
+    shard.advance(1);
+    assert!(shard.structures("bob").is_empty());
+    assert_eq!(ended(&shard), Some((21, names(&["alice"]))));
+    let state = shard.world().get_resource::<Match>().unwrap().clone();
+    assert_eq!(state.phase, MatchPhase::Ended);
+    assert_eq!(state.result.unwrap().winners, names(&["alice"]));
+}
+
+#[test]
+fn the_first_to_reach_the_score_wins() {
+    let mut shard = Harness::new(with_rules(2, WinCondition::FirstToScore(10)));
+    let last = Arc::new(Mutex::new(0));
+    let invoked = last.clone();
+    shard.colonies().script("bob", move |tick| {
+        *invoked.lock().unwrap() = tick.tick;
+        if tick.tick == 1 {
+            vec![ColonyCommand::ConstructUnit(
+                1,
+                UnitType::Mine(OreType::new("Wasmium")),
+            )]
+        } else {
+            Vec::new()
+        }
+    });
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+
+    // Ten points for the mine, completed during tick 30
+    shard.advance(30);
+    assert_eq!(ended(&shard), None);
+    shard.advance(1);
+    assert_eq!(ended(&shard), Some((30, names(&["bob"]))));
+
+    // Colonies aren't invoked once the match is over
+    shard.advance(5);
+    assert_eq!(*last.lock().unwrap(), 30);
+}
+
+#[test]
+fn players_tied_for_the_highest_score_at_the_end_all_win() {
+    let dir = std::env::temp_dir().join(format!("wcshard-results-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("results.json");
+    let mut shard = Harness::new(with_rules(2, WinCondition::HighestScoreAt(5)));
+    shard.world().insert_resource(ResultsFile(path.clone()));
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+
+    shard.advance(6);
+    assert_eq!(ended(&shard), Some((5, names(&["alice", "bob"]))));
+    let results: MatchResult = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(results.tick, 5);
+    assert_eq!(results.winners, names(&["alice", "bob"]));
+    assert_eq!(results.scores.len(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,233 @@
+//! Players joining and leaving: from the roster, and through signed requests from the lattice
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use wascap::jwt::{Actor, Claims};
+use wascap::prelude::KeyPair;
+use wasmcolonies_protocol::{serialize, MembershipRequest, SignedMembershipRequest};
+use wcshard::admission::{AdmissionPolicy, COLONY_TAG};
+use wcshard::harness::Harness;
+use wcshard::lobby::{Gatekeeper, Membership};
+use wcshard::roster::{Roster, RosterEntry};
+use wcshard::rules::GameParameters;
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+/// A colony module's key, and its claims token issued by `issuer`
+fn colony(issuer: &KeyPair) -> (KeyPair, String) {
+    let module = KeyPair::new_module();
+    let claims = Claims {
+        expires: None,
+        id: "colony".to_string(),
+        issued_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        issuer: issuer.public_key(),
+        subject: module.public_key(),
+        not_before: None,
+        metadata: Some(Actor {
+            name: Some("Colony".to_string()),
+            module_hash: String::new(),
+            tags: Some(vec![COLONY_TAG.to_string()]),
+            caps: None,
+            rev: None,
+            ver: None,
+            call_alias: None,
+            provider: false,
+        }),
+    };
+    let jwt = claims.encode(issuer).unwrap();
+    (module, jwt)
+}
+
+fn signed(request: MembershipRequest, key: &KeyPair) -> SignedMembershipRequest {
+    let request = serialize(request).unwrap();
+    SignedMembershipRequest {
+        signature: key.sign(&request).unwrap(),
+        request,
+    }
+}
+
+fn join(player_id: &str, jwt: &str, nonce: &str) -> MembershipRequest {
+    MembershipRequest::Join {
+        player_id: player_id.to_string(),
+        colony_jwt: jwt.to_string(),
+        start: None,
+        nonce: nonce.to_string(),
+    }
+}
+
+fn leave(player_id: &str, key: &KeyPair, nonce: &str) -> MembershipRequest {
+    MembershipRequest::Leave {
+        player_id: player_id.to_string(),
+        actor_key: key.public_key(),
+        nonce: nonce.to_string(),
+    }
+}
+
+#[test]
+fn joining_colonies_must_sign_with_their_module_key() {
+    let issuer = KeyPair::new_account();
+    let (module, jwt) = colony(&issuer);
+    let mut gatekeeper = Gatekeeper::new(AdmissionPolicy::default());
+
+    // Anyone can copy a colony's token, but only its owner holds the module key
+    let impostor = KeyPair::new_module();
+    assert!(gatekeeper
+        .admit(&signed(join("alice", &jwt, "1"), &impostor))
+        .is_err());
+
+    match gatekeeper.admit(&signed(join("alice", &jwt, "2"), &module)) {
+        Ok(Membership::Join { player, .. }) => {
+            assert_eq!(player.id, "alice");
+            assert_eq!(player.actor_key, module.public_key());
+        }
+        other => panic!("expected alice to join, got {:?}", other),
+    }
+}
+
+#[test]
+fn tampered_requests_are_refused() {
+    let issuer = KeyPair::new_account();
+    let (module, jwt) = colony(&issuer);
+    let mut gatekeeper = Gatekeeper::new(AdmissionPolicy::default());
+
+    let mut request = signed(join("alice", &jwt, "1"), &module);
+    request.request = serialize(join("mallory", &jwt, "1")).unwrap();
+    assert!(gatekeeper.admit(&request).is_err());
+}
+
+#[test]
+fn requests_can_only_be_made_once() {
+    let issuer = KeyPair::new_account();
+    let (module, jwt) = colony(&issuer);
+    let mut gatekeeper = Gatekeeper::new(AdmissionPolicy::default());
+
+    let request = signed(leave("alice", &module, "1"), &module);
+    assert!(gatekeeper.admit(&request).is_ok());
+    assert_eq!(
+        gatekeeper.admit(&request).unwrap_err(),
+        "request has already been made"
+    );
+    assert!(gatekeeper
+        .admit(&signed(join("alice", &jwt, "1"), &module))
+        .is_err());
+}
+
+#[test]
+fn only_the_colony_a_player_joined_with_can_remove_them() {
+    let issuer = KeyPair::new_account();
+    let (alice_key, alice_jwt) = colony(&issuer);
+    let (bob_key, _) = colony(&issuer);
+    let mut gatekeeper = Gatekeeper::new(AdmissionPolicy::default());
+    let mut shard = Harness::new(params());
+    shard.request(
+        gatekeeper
+            .admit(&signed(join("alice", &alice_jwt, "1"), &alice_key))
+            .unwrap(),
+    );
+    shard.advance(2);
+    assert!(shard.player("alice").is_some());
+
+    // Bob's request is genuine, but alice isn't his to remove
+    shard.request(
+        gatekeeper
+            .admit(&signed(leave("alice", &bob_key, "2"), &bob_key))
+            .unwrap(),
+    );
+    shard.advance(1);
+    assert!(shard.player("alice").is_some());
+
+    shard.request(
+        gatekeeper
+            .admit(&signed(leave("alice", &alice_key, "3"), &alice_key))
+            .unwrap(),
+    );
+    shard.advance(1);
+    assert!(shard.player("alice").is_none());
+}
+
+#[test]
+fn players_leave_with_everything_they_own() {
+    let mut shard = Harness::new(params());
+    shard.join("alice", "alice", None);
+    shard.join("bob", "bob", None);
+    shard.advance(2);
+    assert_eq!(shard.structures("alice").len(), 1);
+
+    shard.leave("alice");
+    shard.advance(1);
+    assert!(shard.player("alice").is_none());
+    assert!(shard.structures("alice").is_empty());
+    assert!(shard.player("bob").is_some());
+}
+
+#[test]
+fn players_can_not_join_twice() {
+    let mut shard = Harness::new(params());
+    shard.join("alice", "alice", None);
+    shard.join("alice", "other", None);
+    shard.advance(1);
+    assert_eq!(shard.structures("alice").len(), 1);
+}
+
+#[test]
+fn roster_players_need_a_module_unless_on_a_lattice() {
+    let entry = |id: &str, actor_key: Option<&str>| RosterEntry {
+        id: id.to_string(),
+        actor_key: actor_key.map(|k| k.to_string()),
+        module: None,
+        start: None,
+    };
+    let roster = Roster {
+        players: vec![entry("alice", Some("MALICE")), entry("bob", None)],
+    };
+
+    let admissions = roster.clone().admit(&AdmissionPolicy::default(), true);
+    assert_eq!(admissions.admitted.len(), 1);
+    assert_eq!(admissions.admitted[0].0.actor_key, "MALICE");
+    assert_eq!(admissions.rejected[0].0, "bob");
+
+    let admissions = roster.admit(&AdmissionPolicy::default(), false);
+    assert!(admissions.admitted.is_empty());
+    assert_eq!(
+        admissions.rejected,
+        vec![
+            (
+                "alice".to_string(),
+                "the embedded host needs a colony module".to_string()
+            ),
+            (
+                "bob".to_string(),
+                "no colony module or actor key given".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn rosters_are_read_as_yaml_or_json() {
+    let dir = std::env::temp_dir();
+    let yaml = dir.join(format!("wcshard-roster-{}.yaml", std::process::id()));
+    std::fs::write(
+        &yaml,
+        "players:\n  - id: alice\n    actor_key: MALICE\n    start: { sys: 0, sat: 1, x: 50.0, y: 50.0 }\n",
+    )
+    .unwrap();
+    let json = dir.join(format!("wcshard-roster-{}.json", std::process::id()));
+    std::fs::write(
+        &json,
+        r#"{"players": [{"id": "alice", "actor_key": "MALICE", "start": {"sys": 0, "sat": 1, "x": 50.0, "y": 50.0}}]}"#,
+    )
+    .unwrap();
+
+    let from_yaml = Roster::load_from_file(&yaml).unwrap();
+    let from_json = Roster::load_from_file(&json).unwrap();
+    let _ = std::fs::remove_file(&yaml);
+    let _ = std::fs::remove_file(&json);
+    assert_eq!(from_yaml, from_json);
+    assert_eq!(from_yaml.players[0].start.as_ref().unwrap().sat, 1);
+}
@@ -0,0 +1,85 @@
+//! Game parameters: what's filled in when they're left out, and what's refused
+
+use wasmcolonies_protocol::OreType;
+use wcshard::rules::GameParameters;
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+/// Parameters with nothing but the one setting that has no default
+fn minimal() -> GameParameters {
+    serde_json::from_str(r#"{"construction_times": {"Mine": 30}}"#).unwrap()
+}
+
+#[test]
+fn the_default_parameters_are_valid() {
+    assert_eq!(params().problems(), Vec::<String>::new());
+}
+
+#[test]
+fn default_base_upgrades_are_paid_for_in_default_ores() {
+    let params = minimal();
+    assert_eq!(params.base_upgrades.len(), 2);
+    let problems: Vec<String> = params
+        .problems()
+        .into_iter()
+        .filter(|p| p.starts_with("base_upgrades"))
+        .collect();
+    assert_eq!(problems, Vec::<String>::new());
+}
+
+#[test]
+fn base_upgrades_must_cost_ores_from_the_catalogue() {
+    let mut params = params();
+    params.base_upgrades[1]
+        .cost
+        .insert(OreType::new("Unobtainium"), 1);
+    assert_eq!(
+        params.problems(),
+        vec!["base_upgrades[1] costs Unobtainium, which isn't in ores".to_string()]
+    );
+}
+
+#[test]
+fn base_upgrades_can_not_weaken_a_base() {
+    let mut params = params();
+    params.base_upgrades[1].ar = 100;
+    assert_eq!(
+        params.problems(),
+        vec!["base_upgrades[1] lowers a base's max_hp or ar".to_string()]
+    );
+}
+
+#[test]
+fn parameters_left_to_their_defaults_are_valid() {
+    assert_eq!(minimal().problems(), Vec::<String>::new());
+}
+
+#[test]
+fn default_scoring_weighs_the_default_ores() {
+    let params = minimal();
+    let scored: Vec<&OreType> = params.scoring.ore_collected.keys().collect();
+    let ores: Vec<&OreType> = params.ores.iter().map(|o| &o.name).collect();
+    assert_eq!(scored, ores);
+}
+
+#[test]
+fn collected_ore_weights_can_be_given_by_their_old_name() {
+    let params: GameParameters = serde_json::from_str(
+        r#"{"construction_times": {"Mine": 30}, "scoring": {"ore_mined": {"Wasmium": 3}}}"#,
+    )
+    .unwrap();
+    assert_eq!(params.scoring.ore_collected[&OreType::new("Wasmium")], 3);
+}
+
+#[test]
+fn matches_need_at_least_one_player() {
+    assert_eq!(minimal().match_rules.min_players, 1);
+    let mut params = params();
+    params.match_rules.min_players = 0;
+    assert_eq!(
+        params.problems(),
+        vec!["match.min_players must be at least 1".to_string()]
+    );
+}
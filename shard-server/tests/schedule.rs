@@ -0,0 +1,72 @@
+//! Ticks played on the clock: however far behind the shard falls, it catches up whole ticks
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use wasmcolonies_domain::ColonyEvent;
+use wcshard::harness::ScriptedTransport;
+use wcshard::journal::Journal;
+use wcshard::lobby::{Lobby, Membership};
+use wcshard::player::Player;
+use wcshard::rules::GameParameters;
+use wcshard::tick::GameTick;
+use wcshard::transport::ColonyInvoker;
+use wcshard::ShardPlugin;
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+#[test]
+fn catching_up_plays_every_stage_of_each_tick() {
+    let colonies = ScriptedTransport::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let ticks = seen.clone();
+    colonies.script("alice", move |tick| {
+        ticks.lock().unwrap().push(tick.tick);
+        Vec::new()
+    });
+    let lobby = Lobby::default();
+    lobby
+        .sender()
+        .send(Membership::Join {
+            player: Player {
+                id: "alice".to_string(),
+                actor_key: "alice".to_string(),
+            },
+            start: None,
+        })
+        .unwrap();
+    let mut app = App::build();
+    app.insert_resource(params())
+        .insert_resource(ColonyInvoker::new(colonies))
+        .insert_resource(lobby)
+        .add_plugins(MinimalPlugins)
+        .add_plugin(ShardPlugin::with_step(0.01));
+    let mut app = app.app;
+
+    // The clock starts on the first update, then falls several steps behind
+    app.update();
+    std::thread::sleep(Duration::from_millis(100));
+    app.update();
+
+    let played = app.world.get_resource::<GameTick>().unwrap().0;
+    assert!(played > 2, "only {} ticks were played", played);
+    let finished: Vec<u64> = app
+        .world
+        .get_resource::<Journal>()
+        .unwrap()
+        .0
+        .events()
+        .unwrap()
+        .into_iter()
+        .filter_map(|e| match e {
+            ColonyEvent::TickFinished(tick) => Some(tick),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(finished, (0..played).collect::<Vec<_>>());
+    // The colony is asked for its commands once in every tick after the one it joined in
+    assert_eq!(*seen.lock().unwrap(), (1..played).collect::<Vec<_>>());
+}
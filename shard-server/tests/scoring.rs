@@ -0,0 +1,112 @@
+//! Points scored for what colonies do, and the scoreboard they make up
+
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{ColonyCommand, Location, OreType, UnitKind, UnitType};
+use wcshard::harness::Harness;
+use wcshard::player::{Player, Score};
+use wcshard::rules::GameParameters;
+use wcshard::scoring::{points, scoreboard};
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+fn collected(ore: &str, qty: u32) -> ColonyEvent {
+    ColonyEvent::OreCollected {
+        tick: 1,
+        player_id: "alice".to_string(),
+        location: Location::default(),
+        ore: OreType::new(ore),
+        qty,
+    }
+}
+
+#[test]
+fn collected_ore_scores_by_its_weight() {
+    let weights = params().scoring;
+    assert_eq!(
+        points(&weights, &collected("Wasmium", 10)),
+        Some(("alice", 10))
+    );
+    assert_eq!(
+        points(&weights, &collected("Cranelite", 10)),
+        Some(("alice", 20))
+    );
+    assert_eq!(points(&weights, &collected("Unobtainium", 10)), None);
+}
+
+#[test]
+fn destroying_a_structure_scores_for_the_destroyer() {
+    let weights = params().scoring;
+    let built = ColonyEvent::UnitConstructed {
+        tick: 1,
+        player_id: "alice".to_string(),
+        kind: UnitKind::Cannon,
+        location: Location::default(),
+    };
+    assert_eq!(points(&weights, &built), Some(("alice", 25)));
+    let destroyed = ColonyEvent::StructureDestroyed {
+        tick: 1,
+        player_id: "bob".to_string(),
+        destroyed_by: "alice".to_string(),
+        location: Location::default(),
+    };
+    assert_eq!(points(&weights, &destroyed), Some(("alice", 500)));
+}
+
+#[test]
+fn the_scoreboard_is_highest_first_then_by_player() {
+    let player = |id: &str| Player {
+        id: id.to_string(),
+        actor_key: id.to_string(),
+    };
+    let players = [
+        (player("carol"), Score { value: 5 }),
+        (player("bob"), Score { value: 10 }),
+        (player("alice"), Score { value: 5 }),
+    ];
+    let board: Vec<(String, u64)> = scoreboard(players.iter().map(|(p, s)| (p, s)))
+        .into_iter()
+        .map(|s| (s.player_id, s.score))
+        .collect();
+    assert_eq!(
+        board,
+        vec![
+            ("bob".to_string(), 10),
+            ("alice".to_string(), 5),
+            ("carol".to_string(), 5),
+        ]
+    );
+}
+
+#[test]
+fn colonies_score_for_the_ore_they_collect_not_what_sits_in_their_mines() {
+    let mut shard = Harness::new(params());
+    shard.colonies().script("alice", |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        match tick.tick {
+            1 => vec![ColonyCommand::ConstructUnit(
+                1,
+                UnitType::Mine(OreType::new("Wasmium")),
+            )],
+            40 => view
+                .mines
+                .iter()
+                .map(|m| ColonyCommand::Collect(40, m.id))
+                .collect(),
+            _ => Vec::new(),
+        }
+    });
+    shard.join("alice", "alice", None);
+
+    // Ten points for the mine once it's built, and none for the ore it stockpiles
+    shard.advance(40);
+    let score = |shard: &mut Harness| {
+        let player = shard.player("alice").unwrap();
+        shard.world().get::<Score>(player).unwrap().value
+    };
+    assert_eq!(score(&mut shard), 10);
+    // A point for each of the 100 Wasmium collected during tick 40
+    shard.advance(1);
+    assert_eq!(score(&mut shard), 110);
+}
@@ -0,0 +1,85 @@
+//! Snapshots on disk: which one a restart picks, and the journal that follows it
+
+use std::path::PathBuf;
+
+use wasmcolonies_domain::ColonyEvent;
+use wcshard::harness::Harness;
+use wcshard::rules::GameParameters;
+use wcshard::snapshot::{self, SnapshotSettings, SNAPSHOT_VERSION};
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+/// Plays 60 ticks of a match, snapshotting every 20 into a directory of the test's own.
+/// Returns the directory and the journal
+fn played(name: &str) -> (PathBuf, Vec<ColonyEvent>) {
+    let dir = std::env::temp_dir().join(format!("wcshard-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut shard = Harness::new(params());
+    shard.world().insert_resource(SnapshotSettings {
+        dir: dir.clone(),
+        interval: 20,
+    });
+    shard.join("alice", "alice", None);
+    shard.advance(60);
+    (dir, shard.events())
+}
+
+#[test]
+fn the_nearest_snapshot_at_or_before_a_tick_is_loaded() {
+    let (dir, _) = played("nearest");
+    let nearest = |tick| {
+        snapshot::load_nearest(&dir, tick)
+            .unwrap()
+            .snapshot
+            .map(|s| s.tick)
+    };
+    assert_eq!(nearest(60), Some(60));
+    assert_eq!(nearest(59), Some(40));
+    assert_eq!(nearest(19), None);
+
+    assert_eq!(snapshot::discard_after(&dir, 20).unwrap(), 2);
+    assert_eq!(nearest(60), Some(20));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn snapshots_of_another_version_are_skipped() {
+    let (dir, _) = played("versions");
+    let (_, latest) = snapshot::list(&dir).unwrap().pop().unwrap();
+    let mut json: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&latest).unwrap()).unwrap();
+    json["version"] = serde_json::json!(SNAPSHOT_VERSION + 1);
+    std::fs::write(&latest, json.to_string()).unwrap();
+
+    let nearest = snapshot::load_nearest(&dir, 60).unwrap();
+    assert_eq!(nearest.skipped, vec![(latest, SNAPSHOT_VERSION + 1)]);
+    assert_eq!(nearest.snapshot.unwrap().tick, 40);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_journal_after_a_snapshot_starts_with_the_tick_it_resumes_at() {
+    let (dir, events) = played("journal");
+    let snapshot = snapshot::load_nearest(&dir, 40).unwrap().snapshot.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let after = snapshot::events_after(events.clone(), &snapshot).unwrap();
+    let finished: Vec<u64> = after
+        .iter()
+        .filter_map(|e| match e {
+            ColonyEvent::TickFinished(t) => Some(*t),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(finished, (40..60).collect::<Vec<u64>>());
+
+    // A journal cut short before the snapshot can't be replayed on top of it
+    let truncated: Vec<ColonyEvent> = events
+        .into_iter()
+        .take_while(|e| *e != ColonyEvent::TickFinished(30))
+        .collect();
+    assert!(snapshot::events_after(truncated, &snapshot).is_err());
+}
@@ -0,0 +1,183 @@
+//! Units launched from one satellite, in transit, and landing on another
+
+use std::sync::{Arc, Mutex};
+
+use wasmcolonies_domain::ColonyEvent;
+use wasmcolonies_protocol::{
+    ColonyCommand, GameStateColonyView, Location, SatelliteView, TransitView, UnitType,
+};
+use wcshard::harness::Harness;
+use wcshard::rules::GameParameters;
+
+fn params() -> GameParameters {
+    serde_json::from_str(include_str!("../default_params.json")).unwrap()
+}
+
+/// The laser is built during tick 20, so it's first on a satellite to launch in tick 21
+const LAUNCH_TICK: u64 = 21;
+
+/// The satellite the player's base is on
+fn home(view: &GameStateColonyView) -> &SatelliteView {
+    let base = &view.structures[0].location;
+    view.satellites
+        .iter()
+        .find(|s| (s.sys, s.sat) == (base.sys, base.sat))
+        .unwrap()
+}
+
+/// Plays a colony that builds a laser and launches it to wherever `destination` picks,
+/// returning every transit it saw in its views
+fn launch<F>(shard: &mut Harness, destination: F) -> Arc<Mutex<Vec<TransitView>>>
+where
+    F: Fn(&GameStateColonyView) -> Location + Send + 'static,
+{
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let transits = seen.clone();
+    shard.colonies().script("alice", move |tick| {
+        let view = tick.game_state.as_ref().unwrap();
+        transits
+            .lock()
+            .unwrap()
+            .extend(view.transits.iter().cloned());
+        match tick.tick {
+            1 => vec![ColonyCommand::ConstructUnit(1, UnitType::Laser)],
+            LAUNCH_TICK => view
+                .mobiles
+                .iter()
+                .map(|m| ColonyCommand::Launch(LAUNCH_TICK, m.id, destination(view)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    });
+    shard.join("alice", "alice", None);
+    shard.advance(LAUNCH_TICK + 1);
+    seen
+}
+
+/// How long each launch said its unit would be in transit
+fn launches(shard: &Harness) -> Vec<u64> {
+    shard
+        .events()
+        .into_iter()
+        .filter_map(|e| match e {
+            ColonyEvent::UnitLaunched { arrive_in, .. } => Some(arrive_in),
+            _ => None,
+        })
+        .collect()
+}
+
+fn location(satellite: &SatelliteView, x: f32, y: f32) -> Location {
+    Location {
+        sys: satellite.sys,
+        sat: satellite.sat,
+        x,
+        y,
+    }
+}
+
+#[test]
+fn units_cross_their_system_in_the_time_between_orbits() {
+    let mut shard = Harness::new(params());
+    let trip = Arc::new(Mutex::new(None));
+    let planned = trip.clone();
+    let seen = launch(&mut shard, move |view| {
+        let home = home(view);
+        let other = view
+            .satellites
+            .iter()
+            .find(|s| s.sys == home.sys && s.sat != home.sat)
+            .unwrap();
+        // Ten ticks to launch, and twenty for every AU between the orbits
+        let ticks = 10 + ((home.orbit - other.orbit).abs() * 20.).ceil() as u64;
+        *planned.lock().unwrap() = Some(ticks);
+        location(other, 50., 60.)
+    });
+    let ticks = trip.lock().unwrap().unwrap();
+    assert_eq!(launches(&shard), vec![ticks]);
+    assert!(shard.mobiles("alice").is_empty());
+    assert_eq!(shard.transits("alice").len(), 1);
+
+    // The unit counts down the trip in its owner's view, one tick at a time
+    shard.advance(1);
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].arrives_in, ticks - 1);
+
+    // It lands once the trip's last tick has been played
+    shard.advance(ticks - 3);
+    assert_eq!(shard.transits("alice").len(), 1);
+    shard.advance(1);
+    assert!(shard.transits("alice").is_empty());
+    let mobiles = shard.mobiles("alice");
+    assert_eq!(mobiles.len(), 1);
+    let landed = &mobiles[0].2;
+    let destination = &seen[0].destination;
+    assert_eq!(
+        (landed.sys, landed.sat, landed.x, landed.y),
+        (destination.sys, destination.sat, 50., 60.)
+    );
+}
+
+#[test]
+fn units_cross_to_other_systems_by_way_of_their_stars() {
+    let mut shard = Harness::new(params());
+    let trip = Arc::new(Mutex::new(None));
+    let planned = trip.clone();
+    launch(&mut shard, move |view| {
+        let home = home(view);
+        let other = view.satellites.iter().find(|s| s.sys != home.sys).unwrap();
+        let system = |index| view.systems.iter().find(|s| s.index == index).unwrap();
+        let (a, b) = (system(home.sys), system(other.sys));
+        let light_years = ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+        // Out to the star, across to the other one at ten ticks a light year, and back out
+        let crossing = (home.orbit + other.orbit) * 20. + light_years * 10.;
+        *planned.lock().unwrap() = Some(10 + crossing.ceil() as u64);
+        location(other, 50., 60.)
+    });
+    let ticks = trip.lock().unwrap().unwrap();
+    assert_eq!(launches(&shard), vec![ticks]);
+    assert_eq!(shard.transits("alice").len(), 1);
+}
+
+#[test]
+fn units_land_within_the_surface_they_were_sent_to() {
+    let mut shard = Harness::new(params());
+    let target = Arc::new(Mutex::new(None));
+    let chosen = target.clone();
+    launch(&mut shard, move |view| {
+        let home = home(view);
+        let other = view
+            .satellites
+            .iter()
+            .find(|s| s.sys == home.sys && s.sat != home.sat)
+            .unwrap();
+        *chosen.lock().unwrap() = Some(other.clone());
+        location(other, -100., 1e9)
+    });
+    let ticks = launches(&shard)[0];
+    shard.advance(ticks);
+    let satellite = target.lock().unwrap().clone().unwrap();
+    let landed = shard.mobiles("alice")[0].2.clone();
+    assert_eq!((landed.x, landed.y), (0., satellite.height));
+}
+
+#[test]
+fn units_can_only_be_launched_to_another_satellite_that_exists() {
+    // The satellite the unit is already on, and one that isn't there
+    for &missing in [false, true].iter() {
+        let mut shard = Harness::new(params());
+        launch(&mut shard, move |view| {
+            let home = home(view);
+            let sat = if missing { 200 } else { home.sat };
+            Location {
+                sys: home.sys,
+                sat,
+                x: 50.,
+                y: 60.,
+            }
+        });
+        assert!(launches(&shard).is_empty());
+        assert!(shard.transits("alice").is_empty());
+        assert_eq!(shard.mobiles("alice").len(), 1);
+    }
+}
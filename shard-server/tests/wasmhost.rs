@@ -0,0 +1,188 @@
+//! Colony modules run by the embedded host, and the limits they're held to
+
+use std::time::Duration;
+
+use wasmcolonies_protocol::PlayerTick;
+use wcshard::rules::ColonyLimits;
+use wcshard::transport::{ColonyFault, ColonyTransport, LocalTransport};
+use wcshard::wasmhost::EmbeddedHost;
+
+/// Function type of `__guest_call`: (operation length, payload length) -> success
+const GUEST_CALL_TYPE: [u8; 6] = [0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f];
+/// Function type of the host calls that take a pointer and a length
+const PTR_LEN_TYPE: [u8; 5] = [0x60, 0x02, 0x7f, 0x7f, 0x00];
+
+fn leb(mut n: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn vector(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut bytes = leb(items.len() as u32);
+    for item in items {
+        bytes.extend(item);
+    }
+    bytes
+}
+
+fn name(s: &str) -> Vec<u8> {
+    let mut bytes = leb(s.len() as u32);
+    bytes.extend(s.as_bytes());
+    bytes
+}
+
+/// A waPC guest with one page of memory, whose `__guest_call` runs `body` (with a single i32
+/// local) and may call `__guest_response` as function 0. `data` is placed at address 0
+fn guest(body: &[u8], data: &[u8]) -> Vec<u8> {
+    let section = |id: u8, contents: Vec<u8>| {
+        let mut bytes = vec![id];
+        bytes.extend(leb(contents.len() as u32));
+        bytes.extend(contents);
+        bytes
+    };
+    let mut import = name("wapc");
+    import.extend(name("__guest_response"));
+    import.extend([0x00, 0x01]);
+    let mut memory_export = name("memory");
+    memory_export.extend([0x02, 0x00]);
+    let mut call_export = name("__guest_call");
+    call_export.extend([0x00, 0x01]);
+    let mut code = vec![0x01, 0x01, 0x7f];
+    code.extend(body);
+    code.push(0x0b);
+    let mut segment = vec![0x00, 0x41, 0x00, 0x0b];
+    segment.extend(leb(data.len() as u32));
+    segment.extend(data);
+
+    let mut module = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    module.extend(section(
+        1,
+        vector(vec![GUEST_CALL_TYPE.to_vec(), PTR_LEN_TYPE.to_vec()]),
+    ));
+    module.extend(section(2, vector(vec![import])));
+    module.extend(section(3, vector(vec![vec![0x00]])));
+    module.extend(section(5, vector(vec![vec![0x00, 0x01]])));
+    module.extend(section(7, vector(vec![memory_export, call_export])));
+    module.extend(section(
+        10,
+        vector(vec![[leb(code.len() as u32), code].concat()]),
+    ));
+    module.extend(section(11, vector(vec![segment])));
+    module
+}
+
+/// Starts a colony running the given module, returning the transport its ticks go through
+fn start(limits: ColonyLimits, module: &[u8]) -> LocalTransport {
+    // The transport waits longer than the colony's deadline, so the host is the one to give up
+    let transport = LocalTransport::new(limits.deadline() + Duration::from_secs(5));
+    EmbeddedHost::new(limits)
+        .unwrap()
+        .start_colony(&transport, "colony", module)
+        .unwrap();
+    transport
+}
+
+fn tick(transport: &LocalTransport, tick: u64) -> Result<(), ColonyFault> {
+    let tick = PlayerTick {
+        tick,
+        player_id: "alice".to_string(),
+        game_state: None,
+    };
+    transport.player_tick("colony", tick).map(|_| ())
+}
+
+#[test]
+fn guest_reads_out_of_bounds_trap_instead_of_allocating() {
+    // __guest_response(0, i32::MAX)
+    let body = [
+        0x41, 0x00, 0x41, 0xff, 0xff, 0xff, 0xff, 0x07, 0x10, 0x00, 0x41, 0x01,
+    ];
+    let transport = start(ColonyLimits::default(), &guest(&body, &[]));
+    match tick(&transport, 1) {
+        Err(ColonyFault::Trap(e)) => assert!(
+            e.contains("Guest read of 2147483647 bytes at 0 is out of bounds"),
+            "{}",
+            e
+        ),
+        other => panic!("expected a trap, got {:?}", other),
+    }
+}
+
+/// Loops forever
+const SPIN: [u8; 7] = [0x03, 0x40, 0x0c, 0x00, 0x0b, 0x41, 0x00];
+
+/// Counts down from 3000, spending about 15,000 fuel, then answers with no commands
+const COUNTDOWN: [u8; 25] = [
+    0x41, 0xb8, 0x17, 0x21, 0x00, 0x03, 0x40, 0x20, 0x00, 0x41, 0x01, 0x6b, 0x22, 0x00, 0x0d, 0x00,
+    0x0b, 0x41, 0x00, 0x41, 0x0b, 0x10, 0x00, 0x41, 0x01,
+];
+
+/// `PlayerTickResponse { commands: vec![] }`, as msgpack
+const NO_COMMANDS: [u8; 11] = [
+    0x81, 0xa8, b'c', b'o', b'm', b'm', b'a', b'n', b'd', b's', 0x90,
+];
+
+#[test]
+fn colonies_that_run_too_long_run_out_of_fuel() {
+    let limits = ColonyLimits {
+        fuel: 100_000,
+        deadline_millis: 10_000,
+        ..Default::default()
+    };
+    let transport = start(limits, &guest(&SPIN, &[]));
+    assert_eq!(tick(&transport, 1), Err(ColonyFault::OutOfFuel));
+}
+
+#[test]
+fn every_tick_gets_a_fresh_fuel_budget() {
+    let limits = ColonyLimits {
+        fuel: 10_000,
+        ..Default::default()
+    };
+    let transport = start(limits, &guest(&COUNTDOWN, &NO_COMMANDS));
+    assert_eq!(tick(&transport, 1), Err(ColonyFault::OutOfFuel));
+
+    // Enough for one tick, but not two
+    let limits = ColonyLimits {
+        fuel: 25_000,
+        ..Default::default()
+    };
+    let transport = start(limits, &guest(&COUNTDOWN, &NO_COMMANDS));
+    for t in 1..=3 {
+        assert_eq!(tick(&transport, t), Ok(()));
+    }
+}
+
+#[test]
+fn colonies_are_interrupted_at_their_deadline() {
+    let limits = ColonyLimits {
+        fuel: 1 << 40,
+        deadline_millis: 100,
+        ..Default::default()
+    };
+    let transport = start(limits, &guest(&SPIN, &[]));
+    assert_eq!(tick(&transport, 1), Err(ColonyFault::Timeout));
+    assert_eq!(tick(&transport, 2), Err(ColonyFault::Timeout));
+}
+
+#[test]
+fn memory_can_not_grow_past_its_cap() {
+    // Traps unless memory.grow(1000) succeeds, as a guest's allocator would
+    let body = [
+        0x41, 0xe8, 0x07, 0x40, 0x00, 0x41, 0x7f, 0x46, 0x04, 0x40, 0x00, 0x0b, 0x41, 0x01,
+    ];
+    let limits = ColonyLimits {
+        memory_pages: 16,
+        ..Default::default()
+    };
+    let transport = start(limits, &guest(&body, &[]));
+    assert_eq!(tick(&transport, 1), Err(ColonyFault::OutOfMemory));
+}